@@ -4,6 +4,9 @@ use std::io::{Result, Write};
 fn main() {
     println!("cargo:rerun-if-changed=../user/src/");
     println!("cargo:rerun-if-changed={}", TARGET_PATH);
+    println!("cargo:rerun-if-env-changed=SCHED");
+    println!("cargo:rerun-if-env-changed=INIT_ENV");
+    println!("cargo:rerun-if-env-changed=MEM");
     insert_app_data().unwrap();
 }
 
@@ -1,14 +1,100 @@
 // 常数
 
 pub const USER_STACK_SIZE: usize = 4096 * 2;
+// mmap由内核选地址时，默认从这里往上找空闲的范围，在用户堆和用户栈之间
+pub const USER_MMAP_BASE: usize = 0x10_0000_0000;
+// 用户栈的栈顶，栈从这里向下增长，初始大小为USER_STACK_SIZE
+pub const USER_STACK_TOP: usize = 0x20_0000_0000;
+// 用户栈默认最多能增长到的大小
+pub const USER_STACK_LIMIT: usize = 0x80_0000;
+// 用户栈能增长到的范围下面保留的保护区大小，落在这里的访问当作栈溢出
+pub const USER_STACK_GUARD_SIZE: usize = 4096 * 16;
+// exec的参数和环境变量字符串连同argv、envp数组最多占用的字节数，不超过用户栈的一半
+pub const ARG_MAX: usize = USER_STACK_SIZE / 2;
+// 默认的内核栈大小
 pub const KERNEL_STACK_SIZE: usize = 4096 * 2;
+// 单个内核栈的最大大小，每个内核栈占一个这么大再加一页保护页的槽位
+pub const KERNEL_STACK_MAX_SIZE: usize = 4096 * 8;
 pub const KERNEL_HEAP_SIZE: usize = 0x30_0000;
 pub const MEMORY_END: usize = 0x88000000;
+// 交换区，用MEMORY_END之后的内存模拟交换设备，qemu要给出足够的内存
+pub const SWAP_START: usize = MEMORY_END;
+pub const SWAP_SIZE: usize = 0x800_0000;
+// qemu的内存大小，编译时通过环境变量MEM给出，写法和qemu的-m参数一样，没给时按256M算
+pub const MEM_SIZE: usize = match option_env!("MEM") {
+    Some(mem) => parse_mem_size(mem),
+    None => 256 << 20,
+};
+// qemu virt平台的内存从0x80000000开始，要能放下MEMORY_END之前的内存和后面的交换区
+const _: () = assert!(
+    0x8000_0000 + MEM_SIZE >= SWAP_START + SWAP_SIZE,
+    "MEM is too small to hold MEMORY_END and the swap area"
+);
+// 空闲页帧少于这么多时，分配用户页之前先换出一些页，给页表等留出余量
+pub const FRAME_LOW_WATERMARK: usize = 16;
+// 每次页面置换最多换出的页数
+pub const RECLAIM_BATCH: usize = 32;
 pub const PAGE_SIZE: usize = 0x1000;
 pub const PAGE_SIZE_BITS: usize = 0xc;
 pub const MAX_SYSCALL_NUM: usize = 500;
-pub const BIG_STRIDE: usize = usize::MAX;
+// pid的上限，分配到这里后回绕
+pub const PID_MAX: usize = 0x8000;
+// 支持的最大处理器核数，entry.asm和trap.S按它划出每个核的启动栈和异常栈
+pub const MAX_HARTS: usize = 4;
+// Stride调度的基准步长，优先级至少为2，所以最大步长不超过BIG_STRIDE / 2，
+// 远小于usize范围的一半，长度回绕后仍能正确比较
+pub const BIG_STRIDE: usize = 1 << 32;
+// 调度策略，编译时通过环境变量SCHED选择：fifo、rr、stride、mlfq、lottery
+pub const SCHED_POLICY: &str = match option_env!("SCHED") {
+    Some(policy) => policy,
+    None => "stride",
+};
+// 用户初始进程的环境变量，编译时通过环境变量INIT_ENV给出，以空白分隔的若干KEY=VALUE
+pub const INIT_ENV: &str = match option_env!("INIT_ENV") {
+    Some(env) => env,
+    None => "",
+};
+// 时间片轮转调度的时间片长度，单位为时钟中断次数
+pub const RR_TIME_SLICE: usize = 5;
+// 多级反馈队列各级的时间片长度，从最高级到最低级，单位为时钟中断次数
+pub const MLFQ_TIME_SLICES: [usize; 3] = [1, 2, 4];
+// 多级反馈队列提升所有任务的周期，单位为毫秒
+pub const MLFQ_BOOST_PERIOD_MS: usize = 1000;
+// 彩票调度中新进程的默认彩票数
+pub const DEFAULT_TICKETS: usize = 100;
+// 内核伪随机数发生器的种子
+pub const RANDOM_SEED: u64 = 0x5eed_2022_0411_0001;
 
 pub const TRAMPOLINE: usize = usize::MAX - PAGE_SIZE + 1;
 pub const TRAP_CONTEXT: usize = TRAMPOLINE - PAGE_SIZE;
 pub const CLOCK_FREQ: usize = 12500000;
+// qemu virt平台的sifive_test设备，写入它可以让qemu带着退出码退出
+pub const VIRT_TEST: usize = 0x100000;
+pub const MMIO: &[(usize, usize)] = &[
+    (VIRT_TEST, 0x1000),
+];
+
+// 解析qemu的-m参数，数字后面可以跟K、M、G、T，不跟时以M为单位
+const fn parse_mem_size(mem: &str) -> usize {
+    let bytes = mem.as_bytes();
+    let mut size = 0;
+    let mut i = 0;
+    while i < bytes.len() && bytes[i].is_ascii_digit() {
+        size = size * 10 + (bytes[i] - b'0') as usize;
+        i += 1;
+    }
+    assert!(i > 0, "MEM must start with a number");
+    let shift = if i == bytes.len() {
+        20
+    } else {
+        assert!(i + 1 == bytes.len(), "MEM has an unknown suffix");
+        match bytes[i] {
+            b'K' | b'k' => 10,
+            b'M' | b'm' => 20,
+            b'G' | b'g' => 30,
+            b'T' | b't' => 40,
+            _ => panic!("MEM has an unknown suffix"),
+        }
+    };
+    size << shift
+}
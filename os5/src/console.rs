@@ -3,6 +3,7 @@
 */
 
 use crate::sbi::console_putchar;
+use crate::sync::SpinNoIrq;
 use core::fmt::{self, Write};
 
 struct Stdout;
@@ -16,7 +17,11 @@ impl Write for Stdout {
     }
 }
 
+// 多个核同时输出时，一次print的内容不会被其他核打断
+static STDOUT_LOCK: SpinNoIrq<()> = SpinNoIrq::new(());
+
 pub fn print(args: fmt::Arguments) {
+    let _guard = STDOUT_LOCK.lock();
     Stdout.write_fmt(args).unwrap();
 }
 
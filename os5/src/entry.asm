@@ -1,14 +1,26 @@
     // 入口点，text段，设置内核栈指针，跳转至rust入口
+    // 每个核都从这里进入，a0是核的编号，放进tp里，之后在内核中一直用tp表示当前核
     .section .text.entry
     .globl _start
 _start:
+    mv tp, a0
+    // 超出支持数量的核直接停下
+    li t0, {MAX_HARTS}
+    bgeu a0, t0, park
+    // 每个核使用自己的启动栈，核i的栈顶为boot_stack_top - i * 64KiB
     la sp, boot_stack_top
+    li t0, 4096 * 16
+    mul t0, t0, a0
+    sub sp, sp, t0
     call rust_main
+park:
+    wfi
+    j park
 
-    // 在bss上划定栈
+    // 在bss上划定栈，每个核64KiB
     .section .bss.stack
     .globl boot_stack
 boot_stack:
-    .space 4096 * 16
+    .space 4096 * 16 * {MAX_HARTS}
     .globl boot_stack_top
-boot_stack_top:
\ No newline at end of file
+boot_stack_top:
@@ -13,5 +13,6 @@ fn panic(info: &PanicInfo) -> ! {
     } else {
         println!("[kernel] Panicked: {}", info.message().unwrap());
     }
-    shutdown()
+    // 内核出错，以失败状态关机
+    shutdown(-1)
 }
@@ -4,6 +4,8 @@
 // 启用panic信息与分配错误处理函数
 #![feature(panic_info_message)]
 #![feature(alloc_error_handler)]
+// 汇编中用到config里的常量
+#![feature(asm_const)]
 
 #[macro_use]
 extern crate bitflags;
@@ -12,6 +14,9 @@ extern crate log;
 
 extern crate alloc;
 
+use core::hint::spin_loop;
+use core::sync::atomic::{AtomicBool, Ordering};
+
 #[macro_use]
 mod console;
 mod config;
@@ -19,6 +24,7 @@ mod lang_items;
 mod loader;
 mod logging;
 mod mm;
+mod random;
 mod sbi;
 mod sync;
 mod syscall;
@@ -27,7 +33,7 @@ mod timer;
 mod trap;
 
 // 将入口点与应用导入一起编译
-core::arch::global_asm!(include_str!("entry.asm"));
+core::arch::global_asm!(include_str!("entry.asm"), MAX_HARTS = const config::MAX_HARTS);
 core::arch::global_asm!(include_str!("link_app.S"));
 
 // 清零bss段
@@ -42,18 +48,52 @@ fn clear_bss() {
     }
 }
 
+// 第一个进入rust_main的核负责初始化内核，其余的核等它完成
+// 放在data段，不会被清零bss段清掉
+#[link_section = ".data"]
+static BOOT_HART_CLAIMED: AtomicBool = AtomicBool::new(false);
+#[link_section = ".data"]
+static KERNEL_READY: AtomicBool = AtomicBool::new(false);
+
+// 通过SBI HSM启动其他的核，不存在的核会启动失败，忽略即可
+fn start_other_harts(boot_hart_id: usize) {
+    extern "C" {
+        fn _start();
+    }
+    for hart_id in (0..config::MAX_HARTS).filter(|&id| id != boot_hart_id) {
+        sbi::hart_start(hart_id, _start as usize, 0);
+    }
+}
+
 #[no_mangle]
-pub fn rust_main() -> ! {
+pub fn rust_main(hart_id: usize) -> ! {
+    if BOOT_HART_CLAIMED
+        .compare_exchange(false, true, Ordering::AcqRel, Ordering::Acquire)
+        .is_ok()
+    {
+        boot_main(hart_id);
+    } else {
+        secondary_main(hart_id);
+    }
+    // 进入用户态
+    task::run_tasks();
+    panic!("Unreachable in rust_main!");
+}
+
+// 启动核的初始化
+fn boot_main(hart_id: usize) {
     // 清零bss段
     clear_bss();
     // 开启内核日志
     logging::init();
     // 内核启动
-    println!("[kernel] Hello, world!");
+    println!("[kernel] Hello, world! (boot hart {})", hart_id);
     // 初始化内存管理模块
     mm::init();
     // 测试内存管理模块是否正常启动
     mm::remap_test();
+    // 选择调度策略
+    task::init_scheduler(config::SCHED_POLICY);
     // 初始化任务管理器，启动初始进程
     task::add_initproc();
     // 初始进程启动完毕
@@ -66,7 +106,21 @@ pub fn rust_main() -> ! {
     timer::set_next_trigger();
     // 列出可执行应用
     loader::list_apps();
-    // 进入用户态
-    task::run_tasks();
-    panic!("Unreachable in rust_main!");
+    task::hart_online();
+    // 内核初始化完毕，启动其他的核
+    KERNEL_READY.store(true, Ordering::Release);
+    start_other_harts(hart_id);
+}
+
+// 其他核的初始化，只需要设置本核的页表、陷入入口和时钟中断
+fn secondary_main(hart_id: usize) {
+    while !KERNEL_READY.load(Ordering::Acquire) {
+        spin_loop();
+    }
+    mm::init_secondary();
+    trap::init();
+    trap::enable_timer_interrupt();
+    timer::set_next_trigger();
+    task::hart_online();
+    println!("[kernel] hart {} started", hart_id);
 }
@@ -161,8 +161,11 @@ impl VirtPageNum {
 
 
 // 对于物理地址
-// 获取物理地址的可写入引用
+// 获取物理地址的只读引用与可写入引用
 impl PhysAddr {
+    pub fn get_ref<T>(&self) -> &'static T {
+        unsafe { (self.0 as *const T).as_ref().unwrap() }
+    }
     pub fn get_mut<T>(&self) -> &'static mut T {
         unsafe { (self.0 as *mut T).as_mut().unwrap() }
     }
@@ -1,8 +1,8 @@
 // 实现物理页帧分配器
 
-use super::{PhysAddr, PhysPageNum};
+use super::{reclaim, PhysAddr, PhysPageNum};
 use crate::config::MEMORY_END;
-use crate::sync::UPSafeCell;
+use crate::sync::SpinNoIrq;
 use alloc::vec::Vec;
 use core::fmt::{self, Debug, Formatter};
 use lazy_static::*;
@@ -101,9 +101,9 @@ type FrameAllocatorImpl = StackFrameAllocator;
 
 
 lazy_static! {
-    // 首次访问时初始化页帧分配器，使用内部可变性
-    pub static ref FRAME_ALLOCATOR: UPSafeCell<FrameAllocatorImpl> =
-        unsafe { UPSafeCell::new(FrameAllocatorImpl::new()) };
+    // 首次访问时初始化页帧分配器，多个核共享，用自旋锁保护
+    pub static ref FRAME_ALLOCATOR: SpinNoIrq<FrameAllocatorImpl> =
+        SpinNoIrq::new(FrameAllocatorImpl::new());
 }
 
 // 接口，划定内核以外的区域新建页帧分配器
@@ -113,28 +113,31 @@ pub fn init_frame_allocator() {
         fn ekernel();
     }
     // 内核以外的位置都受这个分配器管理
-    FRAME_ALLOCATOR.exclusive_access().init(
+    FRAME_ALLOCATOR.lock().init(
         PhysAddr::from(ekernel as usize).ceil(),
         PhysAddr::from(MEMORY_END).floor(),
     );
 }
 
 // 接口，获得抽象化的物理页帧
+// 页帧用完时换出一些用户页再试一次，调用者锁着的地址空间换不出来
 pub fn frame_alloc() -> Option<FrameTracker> {
-    FRAME_ALLOCATOR
-        .exclusive_access()
-        .alloc()
-        .map(FrameTracker::new)
+    let ppn = FRAME_ALLOCATOR.lock().alloc();
+    ppn.or_else(|| {
+        reclaim(None);
+        FRAME_ALLOCATOR.lock().alloc()
+    })
+    .map(FrameTracker::new)
 }
 
 // 接口，获得剩余可用页帧数
 pub fn frame_remain_num() -> usize {
-    FRAME_ALLOCATOR.exclusive_access().remain_num()
+    FRAME_ALLOCATOR.lock().remain_num()
 }
 
 // 给Drop使用，回收页帧
 fn frame_dealloc(ppn: PhysPageNum) {
-    FRAME_ALLOCATOR.exclusive_access().dealloc(ppn);
+    FRAME_ALLOCATOR.lock().dealloc(ppn);
 }
 
 #[allow(unused)]
@@ -2,11 +2,30 @@
 
 // 使用第三方库的伙伴分配器
 use crate::config::KERNEL_HEAP_SIZE;
+use crate::sync::{pop_off, push_off};
 use buddy_system_allocator::LockedHeap;
+use core::alloc::{GlobalAlloc, Layout};
+
+// 伙伴分配器自带的锁不关中断，分配和回收期间关闭本核中断，中断处理中也能安全地分配
+struct IrqSafeHeap(LockedHeap);
+
+unsafe impl GlobalAlloc for IrqSafeHeap {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        push_off();
+        let ptr = self.0.alloc(layout);
+        pop_off();
+        ptr
+    }
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        push_off();
+        self.0.dealloc(ptr, layout);
+        pop_off();
+    }
+}
 
 #[global_allocator]
 // 定义内核堆分配器
-static HEAP_ALLOCATOR: LockedHeap = LockedHeap::empty();
+static HEAP_ALLOCATOR: IrqSafeHeap = IrqSafeHeap(LockedHeap::empty());
 
 #[alloc_error_handler]
 // 绑定分配错误语义项，直接panic
@@ -21,6 +40,7 @@ static mut HEAP_SPACE: [u8; KERNEL_HEAP_SIZE] = [0; KERNEL_HEAP_SIZE];
 pub fn init_heap() {
     unsafe {
         HEAP_ALLOCATOR
+            .0
             .lock()
             .init(HEAP_SPACE.as_ptr() as usize, KERNEL_HEAP_SIZE);
     }
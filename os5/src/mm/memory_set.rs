@@ -1,14 +1,20 @@
 // 地址空间抽象的实现，页表给cpu看和用，我们用更高层抽线的地址空间进行内存操作，在这些抽象里自动更新和维护页表的信息即可（达成同步）
 
-use super::{frame_alloc, frame_remain_num, FrameTracker};
+use super::{frame_alloc, frame_remain_num, reclaim, FrameTracker, ShmObject, SwapSlot};
 use super::{PTEFlags, PageTable, PageTableEntry};
 use super::{PhysAddr, PhysPageNum, VirtAddr, VirtPageNum};
 use super::{StepByOne, VPNRange};
-use crate::config::{MEMORY_END, PAGE_SIZE, TRAMPOLINE, TRAP_CONTEXT, USER_STACK_SIZE};
-use crate::sync::UPSafeCell;
+use crate::config::{MEMORY_END, MMIO, PAGE_SIZE, TRAMPOLINE, TRAP_CONTEXT};
+use crate::config::{USER_MMAP_BASE, USER_STACK_GUARD_SIZE, USER_STACK_LIMIT};
+use crate::config::{USER_STACK_SIZE, USER_STACK_TOP};
+use crate::config::{FRAME_LOW_WATERMARK, SWAP_SIZE, SWAP_START};
+use crate::sbi::remote_sfence_vma;
+use crate::sync::SpinNoIrq;
+use crate::task::{hart_id, online_hart_mask};
 use alloc::collections::BTreeMap;
 use alloc::sync::Arc;
 use alloc::vec::Vec;
+use core::sync::atomic::{AtomicUsize, Ordering};
 use lazy_static::*;
 use riscv::register::satp;
 
@@ -27,11 +33,14 @@ extern "C" {
 }
 
 lazy_static! {
-    // 初始化内核地址空间
-    pub static ref KERNEL_SPACE: Arc<UPSafeCell<MemorySet>> =
-        Arc::new(unsafe { UPSafeCell::new(MemorySet::new_kernel()) });
+    // 初始化内核地址空间，各个核分配内核栈时都会修改它，用自旋锁保护
+    pub static ref KERNEL_SPACE: Arc<SpinNoIrq<MemorySet>> =
+        Arc::new(SpinNoIrq::new(MemorySet::new_kernel()));
 }
 
+// 下一个地址空间的编号
+static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
 // 地址空间结构体
 pub struct MemorySet {
     page_table: PageTable, // 地址空间的页表，只掌管页表本身占用的页帧资源
@@ -39,32 +48,67 @@ pub struct MemorySet {
     // 相比页表的按页记录，逻辑段粒度更大，包含[虚拟页号范围)、对应的物理页帧资源
     // （直接用BTree映射了“虚拟页号->物理页帧”。我们用BTree查表操作，页表只是维护给CPU用的）、
     // 这片范围的映射方式、这片范围整体的读写权限
+    stack_top: usize, // 用户栈的栈顶，内核地址空间为0
+    stack_limit: usize, // 用户栈最多能增长到的大小，栈顶往下这么多再往下是保护区
+    heap_bottom: usize, // 用户堆的起始地址，紧接在elf的各段之后，内核地址空间为0
+    brk: usize, // 用户堆的结束地址，通过brk系统调用调整
+    mmap_base: usize, // mmap由内核选地址时从这里往上找空闲的范围
+    id: usize, // 地址空间的编号，页面置换时用来认出调用者已经锁住的地址空间
+    // 使用这个地址空间的任务数，clone共享时加一，任务退出或exec离开时减一
+    // 不能用Arc的引用计数代替，页面置换时会临时持有引用
+    tasks: usize,
+    // 内核最近一次准备好、接下来要直接访问的页的范围[start, end)，页面置换不换出它们，下一次准备时替换掉
+    pinned: (VirtPageNum, VirtPageNum),
 }
 
 // 地址空间方法
 impl MemorySet {
-    // 新建空的地址空间
-    pub fn new_bare() -> Self {
-        Self {
-            page_table: PageTable::new(),
+    // 新建空的地址空间，页帧不够时返回None
+    pub fn new_bare() -> Option<Self> {
+        Some(Self {
+            page_table: PageTable::new()?,
             areas: Vec::new(),
-        }
+            stack_top: 0,
+            stack_limit: 0,
+            heap_bottom: 0,
+            brk: 0,
+            mmap_base: 0,
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            tasks: 1,
+            pinned: (VirtPageNum(0), VirtPageNum(0)),
+        })
+    }
+    // 地址空间的编号
+    pub fn id(&self) -> usize {
+        self.id
+    }
+    // 是否有多个任务在使用这个地址空间
+    pub fn is_shared(&self) -> bool {
+        self.tasks > 1
+    }
+    // 又一个任务开始使用这个地址空间
+    pub fn attach_task(&mut self) {
+        self.tasks += 1;
+    }
+    // 一个任务不再使用这个地址空间
+    pub fn detach_task(&mut self) {
+        self.tasks -= 1;
     }
     // 地址空间token化，方便写入satp
     pub fn token(&self) -> usize {
         self.page_table.token()
     }
-    // 压入一个不用写入数据的逻辑段
+    // 压入一个不用写入数据的逻辑段，页帧不够时返回false
     pub fn insert_framed_area(
         &mut self,
         start_va: VirtAddr,
         end_va: VirtAddr,
         permission: MapPermission,
-    ) {
+    ) -> bool {
         self.push(
             MapArea::new(start_va, end_va, MapType::Framed, permission),
             None,
-        );
+        )
     }
     // 移出指定的逻辑段，使用逻辑段的起始页号完成
     pub fn remove_area_with_start_vpn(&mut self, start_vpn: VirtPageNum) {
@@ -78,28 +122,35 @@ impl MemorySet {
             self.areas.remove(idx);
         }
     }
-    // 压入一个逻辑段，可选写入数据
-    fn push(&mut self, mut map_area: MapArea, data: Option<&[u8]>) {
-        map_area.map(&mut self.page_table);
+    // 压入一个逻辑段，可选写入数据，页帧不够时什么都不压入，返回false
+    fn push(&mut self, mut map_area: MapArea, data: Option<&[u8]>) -> bool {
+        if !map_area.map(&mut self.page_table) {
+            return false;
+        }
         if let Some(data) = data {
             map_area.copy_data(&mut self.page_table, data);
         }
         self.areas.push(map_area);
+        true
+    }
+    // 压入内核的逻辑段，启动时页帧是够的
+    fn push_kernel(&mut self, map_area: MapArea) {
+        assert!(self.push(map_area, None), "no frames left for kernel space");
     }
     // 压入跳板段
-    fn map_trampoline(&mut self) {
+    fn map_trampoline(&mut self) -> bool {
         self.page_table.map(
             VirtAddr::from(TRAMPOLINE).into(),
             PhysAddr::from(strampoline as usize).into(),
             PTEFlags::R | PTEFlags::X,
-        );
+        )
     }
     // 压入内核段
     pub fn new_kernel() -> Self {
         // 新建空内存空间
-        let mut memory_set = Self::new_bare();
+        let mut memory_set = Self::new_bare().unwrap();
         // 压入跳板
-        memory_set.map_trampoline();
+        assert!(memory_set.map_trampoline(), "no frames left for kernel space");
         // 压入内核各段
         info!(".text [{:#x}, {:#x})", stext as usize, etext as usize);
         info!(".rodata [{:#x}, {:#x})", srodata as usize, erodata as usize);
@@ -109,63 +160,69 @@ impl MemorySet {
             sbss_with_stack as usize, ebss as usize
         );
         info!("mapping .text section");
-        memory_set.push(
-            MapArea::new(
-                (stext as usize).into(),
-                (etext as usize).into(),
-                MapType::Identical,
-                MapPermission::R | MapPermission::X,
-            ),
-            None,
-        );
+        memory_set.push_kernel(MapArea::new(
+            (stext as usize).into(),
+            (etext as usize).into(),
+            MapType::Identical,
+            MapPermission::R | MapPermission::X,
+        ));
         info!("mapping .rodata section");
-        memory_set.push(
-            MapArea::new(
-                (srodata as usize).into(),
-                (erodata as usize).into(),
-                MapType::Identical,
-                MapPermission::R,
-            ),
-            None,
-        );
+        memory_set.push_kernel(MapArea::new(
+            (srodata as usize).into(),
+            (erodata as usize).into(),
+            MapType::Identical,
+            MapPermission::R,
+        ));
         info!("mapping .data section");
-        memory_set.push(
-            MapArea::new(
-                (sdata as usize).into(),
-                (edata as usize).into(),
-                MapType::Identical,
-                MapPermission::R | MapPermission::W,
-            ),
-            None,
-        );
+        memory_set.push_kernel(MapArea::new(
+            (sdata as usize).into(),
+            (edata as usize).into(),
+            MapType::Identical,
+            MapPermission::R | MapPermission::W,
+        ));
         info!("mapping .bss section");
-        memory_set.push(
-            MapArea::new(
-                (sbss_with_stack as usize).into(),
-                (ebss as usize).into(),
-                MapType::Identical,
-                MapPermission::R | MapPermission::W,
-            ),
-            None,
-        );
+        memory_set.push_kernel(MapArea::new(
+            (sbss_with_stack as usize).into(),
+            (ebss as usize).into(),
+            MapType::Identical,
+            MapPermission::R | MapPermission::W,
+        ));
         info!("mapping physical memory");
-        memory_set.push(
-            MapArea::new(
-                (ekernel as usize).into(),
-                MEMORY_END.into(),
+        memory_set.push_kernel(MapArea::new(
+            (ekernel as usize).into(),
+            MEMORY_END.into(),
+            MapType::Identical,
+            MapPermission::R | MapPermission::W,
+        ));
+        info!("mapping swap area");
+        memory_set.push_kernel(MapArea::new(
+            SWAP_START.into(),
+            (SWAP_START + SWAP_SIZE).into(),
+            MapType::Identical,
+            MapPermission::R | MapPermission::W,
+        ));
+        info!("mapping memory-mapped registers");
+        for pair in MMIO {
+            memory_set.push_kernel(MapArea::new(
+                (*pair).0.into(),
+                ((*pair).0 + (*pair).1).into(),
                 MapType::Identical,
                 MapPermission::R | MapPermission::W,
-            ),
-            None,
-        );
+            ));
+        }
         memory_set
     }
-    // 使用elf构建应用地址空间
-    pub fn from_elf(elf_data: &[u8]) -> (Self, usize, usize) {
+    // 使用elf构建应用地址空间，elf的段和用户栈的保留范围重叠或者页帧不够时返回失败的原因
+    pub fn from_elf(elf_data: &[u8]) -> Result<(Self, usize, usize, usize), LoadError> {
         // 为应用新建一个地址空间
-        let mut memory_set = Self::new_bare();
+        let mut memory_set = Self::new_bare().ok_or(LoadError::NoMemory)?;
+        // 用户栈放在固定的高地址，缺页时可以向下增长到stack_limit
+        memory_set.stack_top = USER_STACK_TOP;
+        memory_set.stack_limit = USER_STACK_LIMIT;
         // 压入跳板
-        memory_set.map_trampoline();
+        if !memory_set.map_trampoline() {
+            return Err(LoadError::NoMemory);
+        }
 
         // 解析elf
         let elf = xmas_elf::ElfFile::new(elf_data).unwrap();
@@ -176,6 +233,9 @@ impl MemorySet {
         // 获得程序头计数，程序头中包含elf期望如何构建应用的地址空间
         let ph_count = elf_header.pt2.ph_count();
         let mut max_end_vpn = VirtPageNum(0);
+        // 程序头表在地址空间中的位置，给辅助向量用，程序头表没有被装载时为0
+        let ph_offset = elf_header.pt2.ph_offset() as usize;
+        let mut phdr_va = 0;
 
         // 遍历程序头
         for i in 0..ph_count {
@@ -183,7 +243,12 @@ impl MemorySet {
             // 如果是描述逻辑段的程序头，则构建逻辑段并压入
             if ph.get_type().unwrap() == xmas_elf::program::Type::Load {
                 let start_va: VirtAddr = (ph.virtual_addr() as usize).into();
-                let end_va: VirtAddr = ((ph.virtual_addr() + ph.mem_size()) as usize).into();
+                // 分配页帧之前先检查，段不能占用用户栈的保留范围
+                let end = match ph.virtual_addr().checked_add(ph.mem_size()) {
+                    Some(end) if end as usize <= memory_set.stack_reserved_bottom() => end as usize,
+                    _ => return Err(LoadError::BadElf),
+                };
+                let end_va: VirtAddr = end.into();
                 let mut map_perm = MapPermission::U;
                 let ph_flags = ph.flags();
                 if ph_flags.is_read() {
@@ -195,68 +260,365 @@ impl MemorySet {
                 if ph_flags.is_execute() {
                     map_perm |= MapPermission::X;
                 }
+                // 程序头表落在这个段的文件内容里时，就能算出它被装载到的地址
+                let offset = ph.offset() as usize;
+                if phdr_va == 0 && offset <= ph_offset && ph_offset < offset + ph.file_size() as usize {
+                    phdr_va = ph.virtual_addr() as usize + ph_offset - offset;
+                }
                 let map_area = MapArea::new(start_va, end_va, MapType::Framed, map_perm);
                 max_end_vpn = map_area.vpn_range.get_end();
-                memory_set.push(
+                if !memory_set.push(
                     map_area,
                     Some(&elf.input[ph.offset() as usize..(ph.offset() + ph.file_size()) as usize]),
-                );
+                ) {
+                    return Err(LoadError::NoMemory);
+                }
             }
         }
         // 划一个用户栈
-        let max_end_va: VirtAddr = max_end_vpn.into();
-        let mut user_stack_bottom: usize = max_end_va.into();
-        // 添加栈之间的空隙
-        user_stack_bottom += PAGE_SIZE;
-        let user_stack_top = user_stack_bottom + USER_STACK_SIZE;
-        // 压入用户栈
+        let user_stack_top = USER_STACK_TOP;
+        let user_stack_bottom = user_stack_top - USER_STACK_SIZE;
+        // 压入一开始为空的用户堆，brk时扩展，同样用到哪页才分配哪页
+        let heap_bottom: usize = VirtAddr::from(max_end_vpn).into();
+        memory_set.heap_bottom = heap_bottom;
+        memory_set.brk = heap_bottom;
+        memory_set.mmap_base = USER_MMAP_BASE;
         memory_set.push(
-            MapArea::new(
-                user_stack_bottom.into(),
-                user_stack_top.into(),
-                MapType::Framed,
+            MapArea::new_lazy(
+                heap_bottom.into(),
+                heap_bottom.into(),
                 MapPermission::R | MapPermission::W | MapPermission::U,
             ),
             None,
         );
-        // 压入Trap上下文
+        // 压入用户栈，用到哪页才分配哪页
         memory_set.push(
-            MapArea::new(
-                TRAP_CONTEXT.into(),
-                TRAMPOLINE.into(),
-                MapType::Framed,
-                MapPermission::R | MapPermission::W,
+            MapArea::new_lazy(
+                user_stack_bottom.into(),
+                user_stack_top.into(),
+                MapPermission::R | MapPermission::W | MapPermission::U,
             ),
             None,
         );
-        // 返回结果，memory_set可以得到用户地址空间token，返回里面还包含栈顶和进程入口点
+        // 压入Trap上下文
+        if !memory_set.insert_framed_area(
+            TRAP_CONTEXT.into(),
+            TRAMPOLINE.into(),
+            MapPermission::R | MapPermission::W,
+        ) {
+            return Err(LoadError::NoMemory);
+        }
+        // 返回结果，memory_set可以得到用户地址空间token，返回里面还包含栈顶、进程入口点和程序头表地址
         // 这些信息就可以拿去构建初始的挂起快照了
-        (
+        Ok((
             memory_set,
             user_stack_top,
             elf.header.pt2.entry_point() as usize,
-        )
+            phdr_va,
+        ))
     }
-    // 赋值一个已存在的用户地址空间，用于fork
-    pub fn from_existed_user(user_space: &MemorySet) -> MemorySet {
+    // 复刻一个已存在的用户地址空间，用于fork
+    // cow为真时用户可见的逻辑段和原地址空间共享物理页帧，其中可写的页在两边都改为只读，第一次写时再复制；
+    // 原地址空间还被别的任务共享时，别的核上可能缓存着可写的页表项，cow必须为假，照旧全部复制
+    // Trap上下文总是复制的，内核直接通过物理页帧写它；页帧不够时返回None
+    pub fn from_existed_user(user_space: &mut MemorySet, cow: bool) -> Option<MemorySet> {
         // 新建一个空的地址空间
-        let mut memory_set = Self::new_bare();
+        let mut memory_set = Self::new_bare()?;
+        memory_set.stack_top = user_space.stack_top;
+        memory_set.stack_limit = user_space.stack_limit;
+        memory_set.heap_bottom = user_space.heap_bottom;
+        memory_set.brk = user_space.brk;
+        memory_set.mmap_base = user_space.mmap_base;
         // 压入跳板
-        memory_set.map_trampoline();
+        if !memory_set.map_trampoline() {
+            return None;
+        }
         // 压入各段
-        for area in user_space.areas.iter() {
-            let new_area = MapArea::from_another(area);
-            memory_set.push(new_area, None);
-            // copy data from another space
-            for vpn in area.vpn_range {
-                let src_ppn = user_space.translate(vpn).unwrap().ppn();
-                let dst_ppn = memory_set.translate(vpn).unwrap().ppn();
-                dst_ppn
-                    .get_bytes_array()
-                    .copy_from_slice(src_ppn.get_bytes_array());
+        let MemorySet { page_table, areas, .. } = user_space;
+        for area in areas.iter() {
+            let mut new_area = MapArea::from_another(area);
+            if area.is_shared() {
+                // 共享内存的页帧照旧共享，权限不变
+                for (vpn, frame) in area.data_frames.iter() {
+                    if !memory_set.page_table.map(*vpn, frame.ppn, area.pte_flags()) {
+                        return None;
+                    }
+                    new_area.data_frames.insert(*vpn, frame.clone());
+                }
+                memory_set.areas.push(new_area);
+                continue;
+            }
+            if cow && area.is_user_framed() {
+                // 共享页帧，可写的页两边都去掉写权限
+                let flags = area.pte_flags() - PTEFlags::W;
+                for (vpn, frame) in area.data_frames.iter() {
+                    page_table.remap(*vpn, frame.ppn, flags);
+                    if !memory_set.page_table.map(*vpn, frame.ppn, flags) {
+                        return None;
+                    }
+                    new_area.data_frames.insert(*vpn, frame.clone());
+                }
+            } else {
+                // 只复制在内存中的页，按需分配的逻辑段里还没分配的页不用管
+                for vpn in area.data_frames.keys() {
+                    if !new_area.map_one(&mut memory_set.page_table, *vpn) {
+                        return None;
+                    }
+                }
+                // copy data from another space
+                for vpn in area.data_frames.keys() {
+                    let src_ppn = page_table.translate(*vpn).unwrap().ppn();
+                    let dst_ppn = memory_set.translate(*vpn).unwrap().ppn();
+                    dst_ppn
+                        .get_bytes_array()
+                        .copy_from_slice(src_ppn.get_bytes_array());
+                }
+            }
+            // 换出了的页复制一份交换区里的内容，交换区满了时直接读进新的页帧
+            for (vpn, slot) in area.swapped.iter() {
+                if area.data_frames.contains_key(vpn) {
+                    continue;
+                }
+                match slot.duplicate() {
+                    Some(copy) => {
+                        new_area.swapped.insert(*vpn, copy);
+                    }
+                    None => {
+                        if !new_area.map_one(&mut memory_set.page_table, *vpn) {
+                            return None;
+                        }
+                        slot.read(new_area.data_frames[vpn].ppn);
+                    }
+                }
             }
+            memory_set.areas.push(new_area);
         }
-        memory_set
+        Some(memory_set)
+    }
+    // 处理用户态访问va时的缺页，access是访问方式，返回是否处理了，没处理的就是访存错误
+    // 逻辑段可写但页表项只读的是写时复制的页，只剩自己引用这个页帧时直接恢复写权限，否则复制一份；
+    // 换出了的页从交换区读回来；按需分配的逻辑段中还没有映射的页，第一次访问时分配一个清零的页帧
+    // 分配页帧时可能换出别的页，所以这里按下标而不是引用找逻辑段
+    pub fn handle_page_fault(&mut self, va: usize, access: AccessType) -> bool {
+        let vpn = VirtAddr::from(va).floor();
+        self.grow_stack(vpn, access);
+        let idx = match self.areas.iter().position(|area| area.contains(vpn)) {
+            Some(idx) => idx,
+            None => return false,
+        };
+        let area = &self.areas[idx];
+        if !area.is_user_framed() || !area.map_perm.contains(access.permission()) {
+            return false;
+        }
+        match self.page_table.translate(vpn) {
+            Some(pte) if pte.is_valid() => {
+                if access != AccessType::Write {
+                    return true;
+                }
+                // 交换区里的副本马上就过时了，内核通过页帧写用户内存时不会置上D位，只能现在丢掉
+                self.areas[idx].swapped.remove(&vpn);
+                // 别的任务已经处理过了
+                if pte.writable() {
+                    return true;
+                }
+                if Arc::strong_count(&self.areas[idx].data_frames[&vpn]) > 1 {
+                    let new_frame = match self.alloc_frame() {
+                        Some(frame) => frame,
+                        None => return false,
+                    };
+                    let area = &mut self.areas[idx];
+                    // 共享它的地址空间都退出了时，这一页可能刚被换出，重新处理一次
+                    let src_ppn = match area.data_frames.get(&vpn) {
+                        Some(frame) => frame.ppn,
+                        None => return self.handle_page_fault(va, access),
+                    };
+                    new_frame
+                        .ppn
+                        .get_bytes_array()
+                        .copy_from_slice(src_ppn.get_bytes_array());
+                    area.data_frames.insert(vpn, Arc::new(new_frame));
+                }
+                let area = &self.areas[idx];
+                self.page_table.remap(vpn, area.data_frames[&vpn].ppn, area.pte_flags());
+                true
+            }
+            _ if area.lazy || area.swapped.contains_key(&vpn) => {
+                // 页帧分配器分配出来的页帧已经清零了
+                let frame = match self.alloc_frame() {
+                    Some(frame) => frame,
+                    None => return false,
+                };
+                let area = &mut self.areas[idx];
+                if let Some(slot) = area.swapped.get(&vpn) {
+                    slot.read(frame.ppn);
+                }
+                // 置上A位，刚准备好的页不会马上被换出
+                if !self.page_table.map(vpn, frame.ppn, area.pte_flags() | PTEFlags::A) {
+                    return false;
+                }
+                // 要写的页读回来马上就脏了，交换区里的副本没用了；只读的留着，再换出时不用重写
+                if access == AccessType::Write {
+                    area.swapped.remove(&vpn);
+                }
+                area.data_frames.insert(vpn, Arc::new(frame));
+                true
+            }
+            _ => false,
+        }
+    }
+    // 为用户页分配页帧，空闲页帧不多时先换出一些页，给页表等内核要用的页帧留出余量
+    fn alloc_frame(&mut self) -> Option<FrameTracker> {
+        if frame_remain_num() < FRAME_LOW_WATERMARK {
+            reclaim(Some(self));
+        }
+        frame_alloc()
+    }
+    // 时钟算法，从from开始按地址顺序扫描可以换出的页，最近访问过的清掉A位再给一次机会，
+    // 没访问过的换出，换出want页就停下，返回换出的页数和下次开始的位置，扫到末尾时位置为None
+    // 和别的逻辑段共享页帧的页、共享内存、内核直接访问的Trap上下文和钉住的页不换出
+    pub fn clock_scan(&mut self, from: VirtPageNum, want: usize) -> (usize, Option<VirtPageNum>) {
+        let (pin_start, pin_end) = self.pinned;
+        let MemorySet { page_table, areas, .. } = self;
+        let mut order: Vec<usize> = (0..areas.len())
+            .filter(|&i| areas[i].is_user_framed() && !areas[i].is_shared())
+            .collect();
+        order.sort_by_key(|&i| areas[i].vpn_range.get_start());
+        let mut victims = Vec::new();
+        let mut next = None;
+        'scan: for i in order {
+            for (vpn, frame) in areas[i].data_frames.range(from..) {
+                if victims.len() == want {
+                    next = Some(*vpn);
+                    break 'scan;
+                }
+                if (pin_start <= *vpn && *vpn < pin_end) || Arc::strong_count(frame) > 1 {
+                    continue;
+                }
+                if page_table.test_and_clear_accessed(*vpn) {
+                    continue;
+                }
+                victims.push((i, *vpn, page_table.take(*vpn)));
+            }
+        }
+        if victims.is_empty() {
+            return (0, next);
+        }
+        // 先让所有核都不再用这些页表项，再把页的内容写出去
+        self.flush_tlb();
+        let MemorySet { page_table, areas, .. } = self;
+        let mut freed = 0;
+        for (i, vpn, pte) in victims {
+            let area = &mut areas[i];
+            let frame = area.data_frames.remove(&vpn).unwrap();
+            // 没被写过而且交换区里有副本的页直接丢掉
+            if pte.dirty() || !area.swapped.contains_key(&vpn) {
+                match SwapSlot::alloc() {
+                    Some(slot) => {
+                        slot.write(frame.ppn);
+                        area.swapped.insert(vpn, slot);
+                    }
+                    None => {
+                        // 交换区满了，放回去，页表项刚取下来，不用分配页表
+                        page_table.map(vpn, frame.ppn, pte.flags());
+                        area.data_frames.insert(vpn, frame);
+                        continue;
+                    }
+                }
+            }
+            freed += 1;
+        }
+        (freed, next)
+    }
+    // 用户栈下方、栈能增长到的范围内的页被访问时，把栈的逻辑段向下扩展到这一页，
+    // 栈不允许的访问（比如取指）不扩展，留给调用者按访问越界处理
+    fn grow_stack(&mut self, vpn: VirtPageNum, access: AccessType) {
+        if self.stack_top == 0 || vpn < VirtAddr::from(self.stack_top - self.stack_limit).floor() {
+            return;
+        }
+        let stack_end = VirtAddr::from(self.stack_top).floor();
+        if let Some(area) = self
+            .areas
+            .iter_mut()
+            .find(|area| area.vpn_range.get_end() == stack_end)
+        {
+            if vpn < area.vpn_range.get_start() && area.map_perm.contains(access.permission()) {
+                area.vpn_range = VPNRange::new(vpn, stack_end);
+            }
+        }
+    }
+    // 用户栈的保护区和能增长到的范围的最低地址，别的逻辑段不能占用它上面到栈顶的地址
+    fn stack_reserved_bottom(&self) -> usize {
+        self.stack_top - self.stack_limit - USER_STACK_GUARD_SIZE
+    }
+    // 调整用户栈最多能增长到的大小，limit按页对齐，不能小于初始的用户栈，
+    // 调整后用户栈的保留范围不能碰到别的逻辑段，调整不了时返回false
+    pub fn set_stack_limit(&mut self, limit: usize) -> bool {
+        if limit % PAGE_SIZE != 0
+            || limit < USER_STACK_SIZE
+            || limit + USER_STACK_GUARD_SIZE > self.stack_top
+        {
+            return false;
+        }
+        let reserved_bottom =
+            VirtAddr::from(self.stack_top - limit - USER_STACK_GUARD_SIZE).floor();
+        let stack_end = VirtAddr::from(self.stack_top).floor();
+        if self
+            .overlapping(reserved_bottom, stack_end)
+            .any(|area| area.vpn_range.get_end() != stack_end)
+            || reserved_bottom < VirtAddr::from(self.brk).ceil()
+        {
+            return false;
+        }
+        self.stack_limit = limit;
+        true
+    }
+    // va是否落在用户栈的保护区里，用来报告栈溢出
+    pub fn in_stack_guard(&self, va: usize) -> bool {
+        self.stack_top != 0
+            && self.stack_reserved_bottom() <= va
+            && va < self.stack_top - self.stack_limit
+    }
+    // 内核要访问用户内存[start, start + len)之前调用，按需分配的页先分配出来，
+    // 要写时写时复制的页先复制一份，范围内有不能这样访问的页时返回false
+    // 准备后面的页时可能要换出别的页，已经准备好的页被钉住，不会被换出
+    pub fn prepare_access(&mut self, start: usize, len: usize, access: AccessType) -> bool {
+        let start_vpn = VirtAddr::from(start).floor();
+        let end_vpn = VirtAddr::from(start + len).ceil();
+        self.pinned = (start_vpn, end_vpn);
+        for vpn in VPNRange::new(start_vpn, end_vpn) {
+            if !self.handle_page_fault(VirtAddr::from(vpn).into(), access) {
+                return false;
+            }
+        }
+        true
+    }
+    // 内核要读用户内存中ptr处以0结尾的字符串之前调用，逐页准备直到找到结尾的0
+    pub fn prepare_str(&mut self, ptr: *const u8) -> bool {
+        let mut va = ptr as usize;
+        self.pinned = (VirtAddr::from(va).floor(), VirtAddr::from(va).floor());
+        loop {
+            let vpn = VirtAddr::from(va).floor();
+            self.pinned.1 = VirtPageNum(vpn.0 + 1);
+            if !self.handle_page_fault(va, AccessType::Read) {
+                return false;
+            }
+            let ppn = self.translate(vpn).unwrap().ppn();
+            if ppn.get_bytes_array()[VirtAddr::from(va).page_offset()..].contains(&0) {
+                return true;
+            }
+            va = VirtAddr::from(VirtPageNum(vpn.0 + 1)).into();
+        }
+    }
+    // 把所有写时复制的页都复制出来，地址空间将被多个任务共享之前调用，页帧不够时返回false
+    pub fn break_cow(&mut self) -> bool {
+        let vpns: Vec<VirtPageNum> = self
+            .areas
+            .iter()
+            .filter(|area| area.is_user_framed() && area.map_perm.contains(MapPermission::W))
+            .flat_map(|area| area.data_frames.keys().copied())
+            .collect();
+        vpns.into_iter()
+            .all(|vpn| self.handle_page_fault(VirtAddr::from(vpn).into(), AccessType::Write))
     }
     // 切换到此地址空间
     pub fn activate(&self) {
@@ -272,58 +634,368 @@ impl MemorySet {
     pub fn translate(&self, vpn: VirtPageNum) -> Option<PageTableEntry> {
         self.page_table.translate(vpn)
     }
+    // 为共享这个地址空间的又一个任务分配一页Trap上下文，
+    // 从TRAP_CONTEXT开始向下找第一个没有被逻辑段占用的页，返回它的虚拟地址，页帧不够时返回None
+    pub fn alloc_trap_cx(&mut self) -> Option<usize> {
+        let mut va = TRAP_CONTEXT;
+        loop {
+            let vpn = VirtAddr::from(va).floor();
+            if !self.areas.iter().any(|area| area.contains(vpn)) {
+                break;
+            }
+            va -= PAGE_SIZE;
+        }
+        if !self.insert_framed_area(
+            va.into(),
+            (va + PAGE_SIZE).into(),
+            MapPermission::R | MapPermission::W,
+        ) {
+            return None;
+        }
+        Some(va)
+    }
+    // 任务退出或exec离开共享的地址空间时，释放它的Trap上下文页
+    pub fn dealloc_trap_cx(&mut self, va: usize) {
+        self.remove_area_with_start_vpn(VirtAddr::from(va).floor());
+        self.flush_tlb();
+    }
     // 回收地址空间
     pub fn recycle_data_pages(&mut self) {
         //*self = Self::new_bare();
         self.areas.clear();
     }
+    // 为brk系统调用提供支持，把用户堆的结束地址调整到new_brk，返回调整后的结束地址
+    // new_brk为0或者调整不了时不变，扩展的部分不能碰到别的逻辑段和用户栈的保留范围
+    pub fn brk(&mut self, new_brk: usize) -> usize {
+        if new_brk < self.heap_bottom || new_brk >= self.stack_reserved_bottom() {
+            return self.brk;
+        }
+        let heap_start = VirtAddr::from(self.heap_bottom).floor();
+        let old_end = VirtAddr::from(self.brk).ceil();
+        let new_end = VirtAddr::from(new_brk).ceil();
+        if new_end > old_end && self.overlapping(old_end, new_end).next().is_some() {
+            return self.brk;
+        }
+        let idx = self.heap_area_index().unwrap();
+        let area = &mut self.areas[idx];
+        // 缩小时释放掉已经分配了的页，包括换出了的页
+        let freed: Vec<VirtPageNum> = area
+            .data_frames
+            .keys()
+            .chain(area.swapped.keys())
+            .filter(|vpn| **vpn >= new_end)
+            .copied()
+            .collect();
+        let shrunk = !freed.is_empty();
+        for vpn in freed {
+            area.unmap_one(&mut self.page_table, vpn);
+        }
+        area.vpn_range = VPNRange::new(heap_start, new_end);
+        self.brk = new_brk;
+        if shrunk {
+            self.flush_tlb();
+        }
+        new_brk
+    }
     // 为分配内存的系统调用提供支持
+    // start为0或者port带有MMAP_HINT时由内核选地址，返回选中的地址，start只作为参考；
+    // 否则必须映射在start处，成功时返回0
     pub fn mmap(&mut self, start: usize, len: usize, port: usize) -> isize {
-        if (port & !0b0000_0111 != 0) || (port & 0b0000_0111 == 0) { return -1; }
-        let va_start = VirtAddr::from(start);
-        let va_end = VirtAddr::from(start + len);
-        if va_start.page_offset() != 0 { return -1; }
-        let mut map_perm = MapPermission::U;
-        if port & 0b0000_0001 == 0b0000_0001 {
-            map_perm |= MapPermission::R;
-        }
-        if port & 0b0000_0010 == 0b0000_0010 {
-            map_perm |= MapPermission::W;
-        }
-        if port & 0b0000_0100 == 0b0000_0100 {
-            map_perm |= MapPermission::X;
-        }
-        let map_area = MapArea::new(va_start, va_end, MapType::Framed, map_perm);
-        if VirtAddr::from(len).ceil() > VirtPageNum(frame_remain_num()) { return -1; }
-        for vpn in map_area.vpn_range {
-            if let Some(pte) = self.page_table.find_pte(vpn) { 
-                if pte.is_valid() {
-                    return -1; 
-                }
+        let map_perm = match port_to_perm(port & !MMAP_HINT) {
+            Some(map_perm) => map_perm,
+            None => return -1,
+        };
+        if start == 0 || port & MMAP_HINT != 0 {
+            if len == 0 || len >= self.stack_reserved_bottom() {
+                return -1;
             }
+            let start = match self.find_free_range(start, len) {
+                Some(start) => start,
+                None => return -1,
+            };
+            self.map_lazy(start, len, map_perm);
+            return start as isize;
         }
-        self.push(map_area, None);
+        if VirtAddr::from(start).page_offset() != 0 || !self.is_free_range(start, len) {
+            return -1;
+        }
+        self.map_lazy(start, len, map_perm);
         0
     }
+    // 在[start, start + len)压入一个按需分配的逻辑段，只记下这段范围，第一次访问时才分配页帧
+    fn map_lazy(&mut self, start: usize, len: usize, map_perm: MapPermission) {
+        let map_area = MapArea::new_lazy(start.into(), (start + len).into(), map_perm);
+        self.push(map_area, None);
+        self.merge_areas();
+    }
+    // 把共享内存对象映射到由内核选的地址，返回选中的地址，页表分配不了时返回-1
+    pub fn mmap_shared(&mut self, shm: Arc<ShmObject>, port: usize) -> isize {
+        let map_perm = match port_to_perm(port) {
+            Some(map_perm) => map_perm,
+            None => return -1,
+        };
+        let len = shm.frames.len() * PAGE_SIZE;
+        let start = match self.find_free_range(0, len) {
+            Some(start) => start,
+            None => return -1,
+        };
+        let end = start + len;
+        let mut map_area = MapArea::new(start.into(), end.into(), MapType::Framed, map_perm);
+        for (vpn, frame) in map_area.vpn_range.into_iter().zip(shm.frames.iter()) {
+            if !self.page_table.map(vpn, frame.ppn, map_area.pte_flags()) {
+                map_area.unmap(&mut self.page_table);
+                return -1;
+            }
+            map_area.data_frames.insert(vpn, frame.clone());
+        }
+        map_area.shm = Some(shm);
+        self.areas.push(map_area);
+        start as isize
+    }
+    // [start, start + len)能否用来映射，
+    // 不能和已有的逻辑段重叠，也不能占用用户栈的保护区和能增长到的范围
+    fn is_free_range(&self, start: usize, len: usize) -> bool {
+        if start < self.stack_top && start + len > self.stack_reserved_bottom() {
+            return false;
+        }
+        let start_vpn = VirtAddr::from(start).floor();
+        let end_vpn = VirtAddr::from(start + len).ceil();
+        self.overlapping(start_vpn, end_vpn).next().is_none()
+    }
+    // 找一段能放下len字节的空闲范围，hint按页对齐后可用就用它，
+    // 否则从mmap_base往上，在逻辑段之间找第一个够大的空隙，一直找到用户栈的保留范围为止
+    fn find_free_range(&self, hint: usize, len: usize) -> Option<usize> {
+        let hint = VirtAddr::from(hint).floor().into();
+        if hint != 0 && self.is_free_range(hint, len) {
+            return Some(hint);
+        }
+        let pages = VirtAddr::from(len).ceil().0;
+        let mut ranges: Vec<(usize, usize)> = self
+            .areas
+            .iter()
+            .map(|area| (area.vpn_range.get_start().0, area.vpn_range.get_end().0))
+            .collect();
+        ranges.sort_unstable();
+        let mut candidate = VirtAddr::from(self.mmap_base).floor().0;
+        for (area_start, area_end) in ranges {
+            if area_end <= candidate {
+                continue;
+            }
+            if area_start >= candidate + pages {
+                break;
+            }
+            candidate = area_end;
+        }
+        let start = VirtAddr::from(VirtPageNum(candidate)).into();
+        if start + pages * PAGE_SIZE > self.stack_reserved_bottom() {
+            return None;
+        }
+        Some(start)
+    }
     // 为释放内存的系统调用提供支持
+    // 可以只释放某个逻辑段的一部分，也可以跨过几个逻辑段，逻辑段之间的空洞直接跳过，
+    // 但范围的第一页和最后一页必须映射过，释放越过映射末尾的范围时报错
     pub fn munmap(&mut self, start: usize, len: usize) -> isize {
-        for map_area in self.areas.iter_mut() {
-            if VirtAddr::from(map_area.vpn_range.get_start()) == VirtAddr::from(start) &&
-            VirtAddr::from(map_area.vpn_range.get_end()) == VirtAddr::from(start + len) {
-                map_area.unmap(&mut self.page_table);
-                return 0;
+        let (start_vpn, end_vpn) = match self.adjustable_range(start, len) {
+            Some((start_vpn, end_vpn))
+                if self.is_covered(start_vpn, VirtPageNum(start_vpn.0 + 1))
+                    && self.is_covered(VirtPageNum(end_vpn.0 - 1), end_vpn) =>
+            {
+                (start_vpn, end_vpn)
+            }
+            _ => return -1,
+        };
+        self.split_at(start_vpn);
+        self.split_at(end_vpn);
+        let mut idx = 0;
+        while idx < self.areas.len() {
+            if self.areas[idx].is_within(start_vpn, end_vpn) {
+                let mut area = self.areas.remove(idx);
+                area.unmap(&mut self.page_table);
+            } else {
+                idx += 1;
+            }
+        }
+        self.flush_tlb();
+        0
+    }
+    // 为修改权限的系统调用提供支持，范围的要求和munmap一样，另外范围内不能有空洞
+    // 写时复制共享着的页帧仍然只读，第一次写时再复制
+    pub fn mprotect(&mut self, start: usize, len: usize, port: usize) -> isize {
+        let map_perm = match port_to_perm(port) {
+            Some(map_perm) => map_perm,
+            None => return -1,
+        };
+        let (start_vpn, end_vpn) = match self.adjustable_range(start, len) {
+            Some(range) if self.is_covered(range.0, range.1) => range,
+            _ => return -1,
+        };
+        self.split_at(start_vpn);
+        self.split_at(end_vpn);
+        let MemorySet { page_table, areas, .. } = self;
+        for area in areas.iter_mut().filter(|area| area.is_within(start_vpn, end_vpn)) {
+            area.map_perm = map_perm;
+            for (vpn, frame) in area.data_frames.iter() {
+                let mut flags = area.pte_flags();
+                if Arc::strong_count(frame) > 1 && !area.is_shared() {
+                    flags -= PTEFlags::W;
+                }
+                page_table.remap(*vpn, frame.ppn, flags);
             }
         }
-        -1
+        self.merge_areas();
+        self.flush_tlb();
+        0
+    }
+    // 和[start, end)有交集的逻辑段
+    fn overlapping(&self, start: VirtPageNum, end: VirtPageNum) -> impl Iterator<Item = &MapArea> {
+        self.areas.iter().filter(move |area| {
+            area.vpn_range.get_start() < end && start < area.vpn_range.get_end()
+        })
+    }
+    // 检查[start, start + len)能不能被munmap和mprotect调整，能的话返回它的页号范围
+    // 起始地址要按页对齐，和它有交集的逻辑段都要是用户的逻辑段，而且不能是用户堆和用户栈
+    fn adjustable_range(&self, start: usize, len: usize) -> Option<(VirtPageNum, VirtPageNum)> {
+        if len == 0 || VirtAddr::from(start).page_offset() != 0 {
+            return None;
+        }
+        let start_vpn = VirtAddr::from(start).floor();
+        let end_vpn = VirtAddr::from(start + len).ceil();
+        let heap = self.heap_area_index();
+        let adjustable = self.areas.iter().enumerate().all(|(idx, area)| {
+            let overlaps =
+                area.vpn_range.get_start() < end_vpn && start_vpn < area.vpn_range.get_end();
+            !overlaps || (area.is_user_framed() && Some(idx) != heap && !self.is_stack(area))
+        });
+        if !adjustable {
+            return None;
+        }
+        Some((start_vpn, end_vpn))
+    }
+    // [start, end)是否被逻辑段首尾相接地完全覆盖
+    fn is_covered(&self, start: VirtPageNum, end: VirtPageNum) -> bool {
+        let mut ranges: Vec<(VirtPageNum, VirtPageNum)> = self
+            .overlapping(start, end)
+            .map(|area| (area.vpn_range.get_start(), area.vpn_range.get_end()))
+            .collect();
+        ranges.sort_unstable_by_key(|range| range.0);
+        let mut covered = start;
+        for (area_start, area_end) in ranges {
+            if area_start > covered {
+                return false;
+            }
+            covered = covered.max(area_end);
+        }
+        covered >= end
+    }
+    // 把跨过vpn的逻辑段在vpn处一分为二，之后没有逻辑段跨过vpn
+    fn split_at(&mut self, vpn: VirtPageNum) {
+        if let Some(idx) = self
+            .areas
+            .iter()
+            .position(|area| area.vpn_range.get_start() < vpn && vpn < area.vpn_range.get_end())
+        {
+            let tail = self.areas[idx].split_off(vpn);
+            self.areas.insert(idx + 1, tail);
+        }
+    }
+    // 把首尾相接、属性都一样的逻辑段合并成一个，用户堆和用户栈保持独立
+    // 按起始页号排一次序，能合并的逻辑段就都相邻了，一遍扫过去即可
+    fn merge_areas(&mut self) {
+        self.areas
+            .sort_unstable_by_key(|area| area.vpn_range.get_start());
+        // 用户堆是按位置认出来的，要在挪动逻辑段之前记下
+        let heap = self.heap_area_index();
+        let fixed: Vec<bool> = self
+            .areas
+            .iter()
+            .enumerate()
+            .map(|(idx, area)| Some(idx) == heap || self.is_stack(area))
+            .collect();
+        let mut merged: Vec<MapArea> = Vec::with_capacity(self.areas.len());
+        let mut last_fixed = true;
+        for (area, fixed) in self.areas.drain(..).zip(fixed) {
+            match merged.last_mut() {
+                Some(last) if !last_fixed && !fixed && Self::can_merge(last, &area) => {
+                    last.merge(area)
+                }
+                _ => {
+                    merged.push(area);
+                    last_fixed = fixed;
+                }
+            }
+        }
+        self.areas = merged;
+    }
+    // next是否紧接在area后面并且可以和它合并，不管是不是用户堆和用户栈
+    fn can_merge(area: &MapArea, next: &MapArea) -> bool {
+        area.vpn_range.get_end() == next.vpn_range.get_start()
+            && area.map_type == next.map_type
+            && area.map_perm == next.map_perm
+            && area.lazy == next.lazy
+            && area.is_user_framed()
+            && !area.is_shared()
+            && !next.is_shared()
+    }
+    // 用户堆的逻辑段在areas中的位置，它从heap_bottom开始，一开始是空的
+    fn heap_area_index(&self) -> Option<usize> {
+        if self.heap_bottom == 0 {
+            return None;
+        }
+        let heap_start = VirtAddr::from(self.heap_bottom).floor();
+        self.areas
+            .iter()
+            .position(|area| area.vpn_range.get_start() == heap_start && area.lazy)
+    }
+    // 是否是用户栈的逻辑段，它和用户堆的范围由缺页和brk调整，不能拆开或者合并
+    fn is_stack(&self, area: &MapArea) -> bool {
+        self.stack_top != 0 && area.vpn_range.get_end() == VirtAddr::from(self.stack_top).floor()
+    }
+    // 页表项的权限变小或者映射被撤掉之后调用，清空本核和别的已启动的核的TLB，
+    // 共享这个地址空间的任务可能正在别的核上运行
+    fn flush_tlb(&self) {
+        unsafe {
+            core::arch::asm!("sfence.vma");
+        }
+        let others = online_hart_mask() & !(1 << hart_id());
+        if others != 0 {
+            remote_sfence_vma(others);
+        }
     }
 }
 
+// mmap的port中的这一位表示start只是参考，由内核选地址
+const MMAP_HINT: usize = 1 << 8;
+
+// 把mmap和mprotect的port转换成逻辑段的权限，不合法时返回None
+fn port_to_perm(port: usize) -> Option<MapPermission> {
+    if (port & !0b0000_0111 != 0) || (port & 0b0000_0111 == 0) {
+        return None;
+    }
+    let mut map_perm = MapPermission::U;
+    if port & 0b0000_0001 == 0b0000_0001 {
+        map_perm |= MapPermission::R;
+    }
+    if port & 0b0000_0010 == 0b0000_0010 {
+        map_perm |= MapPermission::W;
+    }
+    if port & 0b0000_0100 == 0b0000_0100 {
+        map_perm |= MapPermission::X;
+    }
+    Some(map_perm)
+}
+
 // 逻辑段结构体
 pub struct MapArea {
     vpn_range: VPNRange, // [范围)
-    data_frames: BTreeMap<VirtPageNum, FrameTracker>, // 管理的物理帧资源，以及对应的虚拟页映射
+    // 管理的物理帧资源，以及对应的虚拟页映射，fork之后可能和别的地址空间共享，引用计数就是共享它的逻辑段数
+    data_frames: BTreeMap<VirtPageNum, Arc<FrameTracker>>,
     map_type: MapType, // 映射类型
     map_perm: MapPermission, // 权限
+    lazy: bool, // 是否按需分配，为真时页帧在第一次访问触发缺页时才分配
+    shm: Option<Arc<ShmObject>>, // 映射的共享内存对象，它的页帧不写时复制，fork后仍然共享
+    // 换出到交换区的页，在内存中的页也可能在这里留着没被改过的副本
+    swapped: BTreeMap<VirtPageNum, SwapSlot>,
 }
 
 // 逻辑段方法
@@ -344,6 +1016,16 @@ impl MapArea {
             data_frames: BTreeMap::new(),
             map_type,
             map_perm,
+            lazy: false,
+            shm: None,
+            swapped: BTreeMap::new(),
+        }
+    }
+    // 新建一个按需分配的逻辑段，只记下范围和权限，页帧在缺页时再分配
+    pub fn new_lazy(start_va: VirtAddr, end_va: VirtAddr, map_perm: MapPermission) -> Self {
+        Self {
+            lazy: true,
+            ..Self::new(start_va, end_va, MapType::Framed, map_perm)
         }
     }
     // 复刻另一个逻辑段，为fork服务
@@ -353,10 +1035,14 @@ impl MapArea {
             data_frames: BTreeMap::new(),
             map_type: another.map_type,
             map_perm: another.map_perm,
+            lazy: another.lazy,
+            shm: another.shm.clone(),
+            swapped: BTreeMap::new(),
         }
     }
     // 添加一个虚拟地址到逻辑段中，根据映射方式进行不同的物理页帧资源分配（到BTree中），同时还要传入一个页表来同步维护
-    pub fn map_one(&mut self, page_table: &mut PageTable, vpn: VirtPageNum) {
+    // 页帧不够时什么都不添加，返回false
+    pub fn map_one(&mut self, page_table: &mut PageTable, vpn: VirtPageNum) -> bool {
         let ppn: PhysPageNum;
         match self.map_type {
             // 恒等映射直接用虚拟地址对应的物理地址，可以和页帧分配器分配出去的重叠映射，这样内核就能控制所有内存
@@ -365,33 +1051,106 @@ impl MapArea {
             }
             // 通过页帧分配器，不同地址空间中的页帧是无交集的
             MapType::Framed => {
-                let frame = frame_alloc().unwrap();
+                let frame = match frame_alloc() {
+                    Some(frame) => frame,
+                    None => return false,
+                };
                 ppn = frame.ppn;
-                self.data_frames.insert(vpn, frame);
+                self.data_frames.insert(vpn, Arc::new(frame));
             }
         }
-        let pte_flags = PTEFlags::from_bits(self.map_perm.bits).unwrap();
-        page_table.map(vpn, ppn, pte_flags);
+        if !page_table.map(vpn, ppn, self.pte_flags()) {
+            self.data_frames.remove(&vpn);
+            return false;
+        }
+        true
+    }
+    // 逻辑段的权限对应的页表项标志位
+    fn pte_flags(&self) -> PTEFlags {
+        PTEFlags::from_bits(self.map_perm.bits).unwrap()
+    }
+    // 虚拟页号是否在逻辑段中
+    fn contains(&self, vpn: VirtPageNum) -> bool {
+        self.vpn_range.get_start() <= vpn && vpn < self.vpn_range.get_end()
+    }
+    // 是否完全落在[start, end)中
+    fn is_within(&self, start: VirtPageNum, end: VirtPageNum) -> bool {
+        start <= self.vpn_range.get_start() && self.vpn_range.get_end() <= end
+    }
+    // 在at处把逻辑段一分为二，自己留下前一半，返回后一半
+    fn split_off(&mut self, at: VirtPageNum) -> MapArea {
+        let tail = MapArea {
+            vpn_range: VPNRange::new(at, self.vpn_range.get_end()),
+            data_frames: self.data_frames.split_off(&at),
+            map_type: self.map_type,
+            map_perm: self.map_perm,
+            lazy: self.lazy,
+            shm: self.shm.clone(),
+            swapped: self.swapped.split_off(&at),
+        };
+        self.vpn_range = VPNRange::new(self.vpn_range.get_start(), at);
+        tail
+    }
+    // 把紧接在后面的逻辑段合并进来
+    fn merge(&mut self, mut next: MapArea) {
+        self.vpn_range = VPNRange::new(self.vpn_range.get_start(), next.vpn_range.get_end());
+        self.data_frames.append(&mut next.data_frames);
+        self.swapped.append(&mut next.swapped);
+    }
+    // 是否映射的是共享内存对象
+    fn is_shared(&self) -> bool {
+        self.shm.is_some()
+    }
+    // 是否是用户可以访问的、用页帧分配器分配的逻辑段，只有它们的页帧会被写时复制共享
+    fn is_user_framed(&self) -> bool {
+        self.map_type == MapType::Framed && self.map_perm.contains(MapPermission::U)
     }
     // 从逻辑段中删除一个虚拟地址，不管是怎么映射直接从Btree里面删掉就行了（同时释放资源），同时还要传入一个页表来同步维护
+    // 换出了的页只释放交换区里的槽位，它没有映射
     pub fn unmap_one(&mut self, page_table: &mut PageTable, vpn: VirtPageNum) {
         #[allow(clippy::single_match)]
         match self.map_type {
             MapType::Framed => {
-                self.data_frames.remove(&vpn);
+                self.swapped.remove(&vpn);
+                if self.data_frames.remove(&vpn).is_none() {
+                    return;
+                }
             }
             _ => {}
         }
         page_table.unmap(vpn);
     }
     // 把新建的逻辑段的地址范围里的地址全都添加到逻辑段BTree中，同时维护页表
-    pub fn map(&mut self, page_table: &mut PageTable) {
+    // 按需分配的逻辑段这时什么都不做；页帧不够时撤掉已经添加的地址，返回false
+    pub fn map(&mut self, page_table: &mut PageTable) -> bool {
+        if self.lazy {
+            return true;
+        }
         for vpn in self.vpn_range {
-            self.map_one(page_table, vpn);
+            if !self.map_one(page_table, vpn) {
+                for mapped in VPNRange::new(self.vpn_range.get_start(), vpn) {
+                    self.unmap_one(page_table, mapped);
+                }
+                return false;
+            }
         }
+        true
     }
     // 从逻辑段BTree中释放所有的映射和物理页帧
+    // 按需分配的逻辑段只有分配过的页有映射
     pub fn unmap(&mut self, page_table: &mut PageTable) {
+        if self.lazy {
+            let vpns: Vec<VirtPageNum> = self
+                .data_frames
+                .keys()
+                .chain(self.swapped.keys())
+                .copied()
+                .collect();
+            for vpn in vpns {
+                self.unmap_one(page_table, vpn);
+            }
+            return;
+        }
         for vpn in self.vpn_range {
             self.unmap_one(page_table, vpn);
         }
@@ -419,6 +1178,34 @@ impl MapArea {
     }
 }
 
+#[derive(Copy, Clone, PartialEq, Debug)]
+// 从elf构建地址空间失败的原因
+pub enum LoadError {
+    // elf的段超出了用户可用的范围
+    BadElf,
+    // 页帧不够
+    NoMemory,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+// 用户对内存的访问方式，缺页时据此检查逻辑段的权限
+pub enum AccessType {
+    Read,
+    Write,
+    Execute,
+}
+
+impl AccessType {
+    // 这种访问需要的权限
+    fn permission(self) -> MapPermission {
+        match self {
+            AccessType::Read => MapPermission::R,
+            AccessType::Write => MapPermission::W,
+            AccessType::Execute => MapPermission::X,
+        }
+    }
+}
+
 #[derive(Copy, Clone, PartialEq, Debug)]
 // 映射类型
 pub enum MapType {
@@ -439,7 +1226,7 @@ bitflags! {
 #[allow(unused)]
 // 测试地址空间模块
 pub fn remap_test() {
-    let mut kernel_space = KERNEL_SPACE.exclusive_access();
+    let mut kernel_space = KERNEL_SPACE.lock();
     let mid_text: VirtAddr = ((stext as usize + etext as usize) / 2).into();
     let mid_rodata: VirtAddr = ((srodata as usize + erodata as usize) / 2).into();
     let mid_data: VirtAddr = ((sdata as usize + edata as usize) / 2).into();
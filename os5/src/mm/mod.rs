@@ -5,14 +5,21 @@ mod frame_allocator;
 mod heap_allocator;
 mod memory_set;
 mod page_table;
+mod shm;
+mod swap;
 
 // 从子模块导出出来，mod.rs作为可见性屏障
 pub use address::{PhysAddr, PhysPageNum, VirtAddr, VirtPageNum};
 use address::{StepByOne, VPNRange};
 pub use frame_allocator::{frame_alloc, frame_remain_num, FrameTracker};
 pub use memory_set::remap_test;
-pub use memory_set::{MapPermission, MemorySet, KERNEL_SPACE};
-pub use page_table::{translated_byte_buffer, translated_refmut, translated_str, PageTableEntry};
+pub use memory_set::{AccessType, LoadError, MapPermission, MemorySet, KERNEL_SPACE};
+pub use shm::{shm_get, ShmObject};
+pub use swap::register_user_space;
+use swap::{reclaim, SwapSlot};
+pub use page_table::{
+    translated_byte_buffer, translated_ref, translated_refmut, translated_str, PageTableEntry,
+};
 use page_table::{PTEFlags, PageTable};
 
 // 初始化内存管理模块
@@ -22,5 +29,10 @@ pub fn init() {
     // 初始化物理页帧分配器
     frame_allocator::init_frame_allocator();
     // 创建内核地址空间，内核页表放入寄存器，启用分页模式
-    KERNEL_SPACE.exclusive_access().activate();
+    KERNEL_SPACE.lock().activate();
+}
+
+// 其他核启动时使用已经建好的内核地址空间，启用分页模式
+pub fn init_secondary() {
+    KERNEL_SPACE.lock().activate();
 }
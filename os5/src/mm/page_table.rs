@@ -5,6 +5,7 @@ use alloc::string::String;
 use alloc::vec;
 use alloc::vec::Vec;
 use bitflags::*;
+use core::sync::atomic::{AtomicUsize, Ordering};
 
 bitflags! {
     // 定义页表项标志位
@@ -63,6 +64,10 @@ impl PageTableEntry {
     pub fn executable(&self) -> bool {
         (self.flags() & PTEFlags::X) != PTEFlags::empty()
     }
+    // 判断页表项映射的页是否被写过，由硬件置位
+    pub fn dirty(&self) -> bool {
+        (self.flags() & PTEFlags::D) != PTEFlags::empty()
+    }
 }
 
 // 页表结构体
@@ -74,14 +79,14 @@ pub struct PageTable {
 
 // 页表方法
 impl PageTable {
-    // 新建空页表，会分配一片页帧存储页表，所携带的资源也就页表本身
-    pub fn new() -> Self {
+    // 新建空页表，会分配一片页帧存储页表，所携带的资源也就页表本身，页帧不够时返回None
+    pub fn new() -> Option<Self> {
         // 分配后是全清零的，这样V标志位也是0
-        let frame = frame_alloc().unwrap();
-        PageTable {
+        let frame = frame_alloc()?;
+        Some(PageTable {
             root_ppn: frame.ppn,
             frames: vec![frame],
-        }
+        })
     }
     // 从token新建页表
     pub fn from_token(satp: usize) -> Self {
@@ -90,7 +95,7 @@ impl PageTable {
             frames: Vec::new(),
         }
     }
-    // 在表里找到虚拟页号对应的表项的位置，没有就创建中间的路径
+    // 在表里找到虚拟页号对应的表项的位置，没有就创建中间的路径，页帧不够创建不了时返回None
     fn find_pte_create(&mut self, vpn: VirtPageNum) -> Option<&mut PageTableEntry> {
         // 虚拟页号切分成三级
         let mut idxs = vpn.indexes();
@@ -109,7 +114,7 @@ impl PageTable {
             // 页表无效则新建页表
             if !pte.is_valid() {
                 // 分配个全0页表
-                let frame = frame_alloc().unwrap();
+                let frame = frame_alloc()?;
                 // 当先表上写上表项，新表项的物理页帧号写进当前表里，并且对应位置V标志位置1
                 *pte = PageTableEntry::new(frame.ppn, PTEFlags::V);
                 // 新表挂载到当前表里
@@ -141,14 +146,18 @@ impl PageTable {
     }
     #[allow(unused)]
     // 在表中添加“虚拟页号->物理页号”的映射，不添加被映射的物理页帧的资源到frame中
-    // 物理页帧的资源由地址空间中的逻辑段的data_frames掌管
-    pub fn map(&mut self, vpn: VirtPageNum, ppn: PhysPageNum, flags: PTEFlags) {
+    // 物理页帧的资源由地址空间中的逻辑段的data_frames掌管，中间的页表分配不了时返回false
+    pub fn map(&mut self, vpn: VirtPageNum, ppn: PhysPageNum, flags: PTEFlags) -> bool {
         // 在表里先找到虚拟页号对应的表项的位置，没有就创建中间的路径
-        let pte = self.find_pte_create(vpn).unwrap();
+        let pte = match self.find_pte_create(vpn) {
+            Some(pte) => pte,
+            None => return false,
+        };
         // 查看找到的位置，如果V是1那就说明已经被映射了，发起报错
         assert!(!pte.is_valid(), "vpn {:?} is mapped before mapping", vpn);
         // V是0表示还没被映射，这样就可以映射了，在表里写入映射信息即可
         *pte = PageTableEntry::new(ppn, flags | PTEFlags::V);
+        true
     }
     #[allow(unused)]
     // 在表中解除“虚拟页号->物理页号”的映射，同样不用考虑被映射的页帧的释放问题，那个由地址空间逻辑段掌控
@@ -160,6 +169,34 @@ impl PageTable {
         // 清零即可
         *pte = PageTableEntry::empty();
     }
+    // 修改已经映射的虚拟页号的表项，换成新的物理页号和标志位，写时复制时使用
+    // 原来的A/D位保留下来，页面置换靠D位判断页是否要重新写回交换区
+    pub fn remap(&mut self, vpn: VirtPageNum, ppn: PhysPageNum, flags: PTEFlags) {
+        let pte = self.find_pte_create(vpn).unwrap();
+        assert!(pte.is_valid(), "vpn {:?} is invalid before remapping", vpn);
+        let kept = pte.flags() & (PTEFlags::A | PTEFlags::D);
+        *pte = PageTableEntry::new(ppn, flags | kept | PTEFlags::V);
+    }
+    // 页表项的原子视图，别的核上的硬件可能同时在设置A/D位
+    fn find_pte_atomic(&self, vpn: VirtPageNum) -> Option<&AtomicUsize> {
+        self.find_pte(vpn)
+            .map(|pte| unsafe { &*(pte as *const PageTableEntry as *const AtomicUsize) })
+    }
+    // 撤掉已经映射的虚拟页号的映射，返回原来的表项，页面置换时使用
+    pub fn take(&mut self, vpn: VirtPageNum) -> PageTableEntry {
+        let bits = self.find_pte_atomic(vpn).unwrap().swap(0, Ordering::AcqRel);
+        let pte = PageTableEntry { bits };
+        assert!(pte.is_valid(), "vpn {:?} is invalid before taking", vpn);
+        pte
+    }
+    // 清掉表项的A位，返回清掉之前是否被访问过，时钟算法使用
+    pub fn test_and_clear_accessed(&mut self, vpn: VirtPageNum) -> bool {
+        let old = self
+            .find_pte_atomic(vpn)
+            .unwrap()
+            .fetch_and(!(PTEFlags::A.bits as usize), Ordering::AcqRel);
+        old & PTEFlags::A.bits as usize != 0
+    }
     // 获得虚拟页号对应的物理页号，查表并转换，可能为None
     pub fn translate(&self, vpn: VirtPageNum) -> Option<PageTableEntry> {
         self.find_pte(vpn).copied()
@@ -224,6 +261,15 @@ pub fn translated_str(token: usize, ptr: *const u8) -> String {
     string
 }
 
+// 从某用户的地址空间（用token指定）中取出某种类型数据的只读引用
+pub fn translated_ref<T>(token: usize, ptr: *const T) -> &'static T {
+    let page_table = PageTable::from_token(token);
+    page_table
+        .translate_va(VirtAddr::from(ptr as usize))
+        .unwrap()
+        .get_ref()
+}
+
 // 从某用户的地址空间（用token指定）中取出某种类型数据供直接读写，会影响用户数据
 // 调用了get_mut()这个向物理地址写入内容的功能，加上自动查表的抽象，实现了向虚拟地址写入内容的效果
 pub fn translated_refmut<T>(token: usize, ptr: *mut T) -> &'static mut T {
@@ -0,0 +1,47 @@
+// 共享内存对象，同一组页帧可以被映射进多个地址空间
+
+use super::{frame_alloc, FrameTracker};
+use crate::sync::SpinNoIrq;
+use alloc::collections::BTreeMap;
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
+use lazy_static::*;
+
+// 共享内存对象，映射它的逻辑段各持有一个引用，最后一个映射消失时页帧随之回收
+pub struct ShmObject {
+    pub frames: Vec<Arc<FrameTracker>>,
+}
+
+lazy_static! {
+    // 按键值找到共享内存对象，只存弱引用，没有映射的对象不会因为在表里而留下来
+    static ref SHM_TABLE: SpinNoIrq<BTreeMap<usize, Weak<ShmObject>>> =
+        SpinNoIrq::new(BTreeMap::new());
+}
+
+impl ShmObject {
+    // 新建一个pages页的共享内存对象，页帧一次分配好，不够时返回None
+    fn new(pages: usize) -> Option<Self> {
+        let mut frames = Vec::with_capacity(pages);
+        for _ in 0..pages {
+            frames.push(Arc::new(frame_alloc()?));
+        }
+        Some(Self { frames })
+    }
+}
+
+// 取得键值为key、大小为pages页的共享内存对象，没有就新建一个
+// key为0时总是新建，这样的对象只能通过fork共享；已有的对象大小不同时返回None
+pub fn shm_get(key: usize, pages: usize) -> Option<Arc<ShmObject>> {
+    if key == 0 {
+        return ShmObject::new(pages).map(Arc::new);
+    }
+    let mut table = SHM_TABLE.lock();
+    if let Some(shm) = table.get(&key).and_then(|shm| shm.upgrade()) {
+        return if shm.frames.len() == pages { Some(shm) } else { None };
+    }
+    // 顺便清掉已经没有映射的对象
+    table.retain(|_, shm| shm.strong_count() > 0);
+    let shm = Arc::new(ShmObject::new(pages)?);
+    table.insert(key, Arc::downgrade(&shm));
+    Some(shm)
+}
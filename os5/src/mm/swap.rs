@@ -0,0 +1,124 @@
+// 页面置换，空闲页帧不够时用时钟算法挑出最近没被访问的用户页，换出到交换区，缺页时再换入
+
+use super::{MemorySet, PhysPageNum, VirtPageNum};
+use crate::config::{PAGE_SIZE, RECLAIM_BATCH, SWAP_SIZE, SWAP_START};
+use crate::sync::SpinNoIrq;
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
+use lazy_static::*;
+
+// 交换区中的一个槽位，存放一页的内容，释放时归还给交换区
+pub struct SwapSlot(usize);
+
+// 交换区的槽位分配器，和栈式页帧分配器一样
+struct SwapAllocator {
+    current: usize,
+    end: usize,
+    recycled: Vec<usize>,
+}
+
+impl SwapAllocator {
+    fn alloc(&mut self) -> Option<usize> {
+        if let Some(slot) = self.recycled.pop() {
+            Some(slot)
+        } else if self.current == self.end {
+            None
+        } else {
+            self.current += 1;
+            Some(self.current - 1)
+        }
+    }
+    fn dealloc(&mut self, slot: usize) {
+        self.recycled.push(slot);
+    }
+}
+
+lazy_static! {
+    static ref SWAP_ALLOCATOR: SpinNoIrq<SwapAllocator> = SpinNoIrq::new(SwapAllocator {
+        current: 0,
+        end: SWAP_SIZE / PAGE_SIZE,
+        recycled: Vec::new(),
+    });
+    // 所有用户地址空间和它们的编号，页面置换时在它们之间轮转
+    static ref USER_SPACES: SpinNoIrq<Vec<(usize, Weak<SpinNoIrq<MemorySet>>)>> =
+        SpinNoIrq::new(Vec::new());
+    // 时钟指针，下一次从第几个地址空间的哪一页开始扫描
+    static ref CLOCK_HAND: SpinNoIrq<(usize, VirtPageNum)> = SpinNoIrq::new((0, VirtPageNum(0)));
+}
+
+impl SwapSlot {
+    // 分配一个槽位，交换区满了时返回None
+    pub fn alloc() -> Option<Self> {
+        SWAP_ALLOCATOR.lock().alloc().map(SwapSlot)
+    }
+    // 槽位在交换区中的物理页号，内核恒等映射了整个交换区
+    fn ppn(&self) -> PhysPageNum {
+        PhysPageNum(SWAP_START / PAGE_SIZE + self.0)
+    }
+    // 把页帧的内容写进槽位
+    pub fn write(&self, ppn: PhysPageNum) {
+        self.ppn().get_bytes_array().copy_from_slice(ppn.get_bytes_array());
+    }
+    // 把槽位的内容读到页帧里
+    pub fn read(&self, ppn: PhysPageNum) {
+        ppn.get_bytes_array().copy_from_slice(self.ppn().get_bytes_array());
+    }
+    // 复制一个内容相同的槽位，fork时使用
+    pub fn duplicate(&self) -> Option<Self> {
+        let slot = Self::alloc()?;
+        slot.write(self.ppn());
+        Some(slot)
+    }
+}
+
+impl Drop for SwapSlot {
+    fn drop(&mut self) {
+        SWAP_ALLOCATOR.lock().dealloc(self.0);
+    }
+}
+
+// 登记一个用户地址空间，它的页之后可以被换出
+pub fn register_user_space(space: &Arc<SpinNoIrq<MemorySet>>) {
+    let id = space.lock().id();
+    let mut spaces = USER_SPACES.lock();
+    spaces.retain(|(_, space)| space.strong_count() > 0);
+    spaces.push((id, Arc::downgrade(space)));
+}
+
+// 用时钟算法换出至多RECLAIM_BATCH页，返回换出的页数
+// current是调用者已经锁住的地址空间，也可以从中换出；别的地址空间锁不住时跳过
+pub fn reclaim(mut current: Option<&mut MemorySet>) -> usize {
+    let spaces = USER_SPACES.lock().clone();
+    if spaces.is_empty() {
+        return 0;
+    }
+    let mut hand = CLOCK_HAND.lock();
+    let mut idx = hand.0 % spaces.len();
+    let mut from = hand.1;
+    let mut freed = 0;
+    // 每个地址空间至多经过两次，第一次清掉访问位，第二次就能换出没再被访问的页
+    for _ in 0..=spaces.len() * 2 {
+        let (id, space) = &spaces[idx];
+        let want = RECLAIM_BATCH - freed;
+        let scanned = match current.as_deref_mut() {
+            Some(current) if current.id() == *id => Some(current.clock_scan(from, want)),
+            _ => space
+                .upgrade()
+                .and_then(|space| space.try_lock().map(|mut space| space.clock_scan(from, want))),
+        };
+        if let Some((count, next)) = scanned {
+            freed += count;
+            if let Some(next) = next {
+                *hand = (idx, next);
+                return freed;
+            }
+        }
+        idx = (idx + 1) % spaces.len();
+        from = VirtPageNum(0);
+        if freed == RECLAIM_BATCH {
+            break;
+        }
+    }
+    *hand = (idx, from);
+    freed
+}
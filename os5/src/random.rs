@@ -0,0 +1,43 @@
+// 内核伪随机数发生器，xorshift64*算法，种子固定，同样的事件序列得到同样的随机数，运行结果可复现
+
+use crate::config::RANDOM_SEED;
+use crate::sync::SpinNoIrq;
+
+// 内核公用的随机数发生器，目前用来给新进程的辅助向量提供随机字节
+// 和彩票调度的发生器错开种子，两者的序列互不相同
+static RANDOM: SpinNoIrq<XorShift64> = SpinNoIrq::new(XorShift64::new(RANDOM_SEED.rotate_left(32)));
+
+// 用随机字节填满buf
+pub fn fill_random(buf: &mut [u8]) {
+    let mut rng = RANDOM.lock();
+    for chunk in buf.chunks_mut(8) {
+        let bytes = rng.next_u64().to_le_bytes();
+        chunk.copy_from_slice(&bytes[..chunk.len()]);
+    }
+}
+
+pub struct XorShift64 {
+    state: u64,
+}
+
+impl XorShift64 {
+    // 用种子新建，种子不能为0
+    pub const fn new(seed: u64) -> Self {
+        Self {
+            state: if seed == 0 { 1 } else { seed },
+        }
+    }
+    // 下一个64位随机数
+    pub fn next_u64(&mut self) -> u64 {
+        let mut x = self.state;
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        self.state = x;
+        x.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+    // [0, bound)范围内的随机数，bound不能为0
+    pub fn next_below(&mut self, bound: usize) -> usize {
+        (self.next_u64() % bound as u64) as usize
+    }
+}
@@ -1,39 +1,117 @@
 #![allow(unused)]
 
+use crate::config::VIRT_TEST;
+use lazy_static::*;
+
 const SBI_SET_TIMER: usize = 0;
 const SBI_CONSOLE_PUTCHAR: usize = 1;
 const SBI_CONSOLE_GETCHAR: usize = 2;
+const SBI_REMOTE_SFENCE_VMA: usize = 6;
 const SBI_SHUTDOWN: usize = 8;
 
+// SBI系统重置扩展
+const SBI_EXT_SRST: usize = 0x5352_5354;
+const SRST_SYSTEM_RESET: usize = 0;
+const SRST_TYPE_SHUTDOWN: usize = 0;
+const SRST_REASON_NONE: usize = 0;
+const SRST_REASON_SYSTEM_FAILURE: usize = 1;
+
+// SBI基础扩展
+const SBI_EXT_BASE: usize = 0x10;
+const BASE_PROBE_EXTENSION: usize = 3;
+
+// SBI远程栅栏扩展
+const SBI_EXT_RFENCE: usize = 0x5246_4E43;
+const RFENCE_SFENCE_VMA: usize = 1;
+
+// SBI核状态管理扩展
+const SBI_EXT_HSM: usize = 0x48_534D;
+const HSM_HART_START: usize = 0;
+
+// sifive_test设备的命令，失败时高16位是退出码
+const VIRT_TEST_FAIL: u32 = 0x3333;
+const VIRT_TEST_PASS: u32 = 0x5555;
+
+// which是扩展号，fid是功能号，旧版的调用功能号都是0
+// 返回a0和a1，旧版的调用在a0中返回值，v0.2的扩展在a0中返回错误码、在a1中返回值
 #[inline(always)]
-fn sbi_call(which: usize, arg0: usize, arg1: usize, arg2: usize) -> usize {
-    let mut ret;
+fn sbi_call(which: usize, fid: usize, args: [usize; 4]) -> (usize, usize) {
+    let (error, value);
     unsafe {
         core::arch::asm!(
-            "li x16, 0",
             "ecall",
-            inlateout("x10") arg0 => ret,
-            in("x11") arg1,
-            in("x12") arg2,
+            inlateout("x10") args[0] => error,
+            inlateout("x11") args[1] => value,
+            in("x12") args[2],
+            in("x13") args[3],
+            in("x16") fid,
             in("x17") which,
         );
     }
-    ret
+    (error, value)
+}
+
+// 查询SBI实现是否提供了某个扩展
+fn probe_extension(which: usize) -> bool {
+    sbi_call(SBI_EXT_BASE, BASE_PROBE_EXTENSION, [which, 0, 0, 0]).1 != 0
+}
+
+lazy_static! {
+    // SBI实现是否提供了远程栅栏扩展，没有时用旧版的调用
+    static ref HAS_RFENCE: bool = probe_extension(SBI_EXT_RFENCE);
 }
 
 pub fn set_timer(timer: usize) {
-    sbi_call(SBI_SET_TIMER, timer, 0, 0);
+    sbi_call(SBI_SET_TIMER, 0, [timer, 0, 0, 0]);
 }
 
 pub fn console_putchar(c: usize) {
-    sbi_call(SBI_CONSOLE_PUTCHAR, c, 0, 0);
+    sbi_call(SBI_CONSOLE_PUTCHAR, 0, [c, 0, 0, 0]);
 }
 
 pub fn console_getchar() -> usize {
-    sbi_call(SBI_CONSOLE_GETCHAR, 0, 0, 0)
+    sbi_call(SBI_CONSOLE_GETCHAR, 0, [0; 4]).0
+}
+
+// 让hart_mask中按位给出的各个核清空TLB，大小为usize::MAX表示整个地址空间
+pub fn remote_sfence_vma(hart_mask: usize) {
+    if *HAS_RFENCE {
+        sbi_call(SBI_EXT_RFENCE, RFENCE_SFENCE_VMA, [hart_mask, 0, 0, usize::MAX]);
+    } else {
+        let hart_mask_ptr = &hart_mask as *const usize as usize;
+        sbi_call(SBI_REMOTE_SFENCE_VMA, 0, [hart_mask_ptr, 0, usize::MAX, 0]);
+    }
 }
 
-pub fn shutdown() -> ! {
-    sbi_call(SBI_SHUTDOWN, 0, 0, 0);
+// 启动一个核，从start_addr开始以S态运行，a0为核编号，a1为opaque，返回SBI错误码
+pub fn hart_start(hart_id: usize, start_addr: usize, opaque: usize) -> usize {
+    sbi_call(SBI_EXT_HSM, HSM_HART_START, [hart_id, start_addr, opaque, 0]).0
+}
+
+// 关机，退出码会成为qemu进程的退出状态
+pub fn shutdown(exit_code: i32) -> ! {
+    // 先用qemu的sifive_test设备，可以带上退出码
+    // qemu的退出状态只有低8位，低8位为0的失败码改成1，不能被当成成功
+    let command = match exit_code {
+        0 => VIRT_TEST_PASS,
+        _ => {
+            let status = match exit_code as u32 & 0xff {
+                0 => 1,
+                status => status,
+            };
+            status << 16 | VIRT_TEST_FAIL
+        }
+    };
+    unsafe {
+        (VIRT_TEST as *mut u32).write_volatile(command);
+    }
+    // 再试SBI SRST扩展，只能区分成功和失败
+    let reason = match exit_code {
+        0 => SRST_REASON_NONE,
+        _ => SRST_REASON_SYSTEM_FAILURE,
+    };
+    sbi_call(SBI_EXT_SRST, SRST_SYSTEM_RESET, [SRST_TYPE_SHUTDOWN, reason, 0, 0]);
+    // 最后用旧版的关机调用
+    sbi_call(SBI_SHUTDOWN, 0, [0; 4]);
     panic!("It should shutdown!");
 }
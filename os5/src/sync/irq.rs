@@ -0,0 +1,139 @@
+// 关中断的嵌套计数，以及只在本核上使用的关中断内部可变单元
+// 持有锁或者独占访问时关闭本核的中断，时钟中断就不会在临界区中打进来再次访问同一个东西
+// 嵌套时只有最外层恢复进入时的sstatus.SIE
+
+use crate::config::MAX_HARTS;
+use crate::task::hart_id;
+use core::cell::{Cell, RefCell, RefMut};
+use core::mem::ManuallyDrop;
+use core::ops::{Deref, DerefMut};
+use riscv::register::sstatus;
+
+// 每个核的关中断状态
+struct IrqState {
+    // 关中断的嵌套层数
+    depth: Cell<usize>,
+    // 最外层关中断之前sstatus.SIE的值
+    saved_sie: Cell<bool>,
+}
+
+struct IrqStates([IrqState; MAX_HARTS]);
+
+// 每个核只访问自己的那一项，而且访问时中断是关着的
+unsafe impl Sync for IrqStates {}
+
+#[allow(clippy::declare_interior_mutable_const)]
+const IRQ_STATE_INIT: IrqState = IrqState {
+    depth: Cell::new(0),
+    saved_sie: Cell::new(false),
+};
+
+static IRQ_STATES: IrqStates = IrqStates([IRQ_STATE_INIT; MAX_HARTS]);
+
+// 关闭本核中断，嵌套层数加一
+pub fn push_off() {
+    let sie = sstatus::read().sie();
+    unsafe {
+        sstatus::clear_sie();
+    }
+    // 中断关了之后才读核编号，之后不会被换到别的核上
+    let state = &IRQ_STATES.0[hart_id()];
+    if state.depth.get() == 0 {
+        state.saved_sie.set(sie);
+    }
+    state.depth.set(state.depth.get() + 1);
+}
+
+// 嵌套层数减一，最外层时恢复中断使能
+pub fn pop_off() {
+    assert!(!sstatus::read().sie(), "pop_off with interrupts enabled");
+    let state = &IRQ_STATES.0[hart_id()];
+    let depth = state.depth.get();
+    assert!(depth > 0, "pop_off without push_off");
+    state.depth.set(depth - 1);
+    if depth == 1 && state.saved_sie.get() {
+        unsafe {
+            sstatus::set_sie();
+        }
+    }
+}
+
+// 关闭本核中断，返回关闭之前的中断使能，之后由restore_irq恢复
+// 已经持有的关中断锁在这之后释放也不会把中断打开，用于交出当前任务到切换走之间不能被抢占的场合
+pub fn disable_irq() -> bool {
+    let sie = sstatus::read().sie();
+    unsafe {
+        sstatus::clear_sie();
+    }
+    let state = &IRQ_STATES.0[hart_id()];
+    if state.depth.get() > 0 {
+        state.saved_sie.replace(false)
+    } else {
+        sie
+    }
+}
+
+// 恢复disable_irq之前的中断使能
+pub fn restore_irq(sie: bool) {
+    if sie {
+        unsafe {
+            sstatus::set_sie();
+        }
+    }
+}
+
+// 本核的关中断嵌套层数，切换任务时必须为0
+pub fn irq_depth() -> usize {
+    IRQ_STATES.0[hart_id()].depth.get()
+}
+
+// 只被一个核访问的内部可变单元，比如每个核的处理器，独占访问期间关闭本核中断
+pub struct IrqSafeCell<T> {
+    inner: RefCell<T>,
+}
+
+// 调用者保证只在一个核上访问
+unsafe impl<T> Sync for IrqSafeCell<T> {}
+
+impl<T> IrqSafeCell<T> {
+    // 新建，调用者保证只在一个核上访问
+    pub unsafe fn new(value: T) -> Self {
+        Self {
+            inner: RefCell::new(value),
+        }
+    }
+    // 借用一个可变引用，引用释放前本核中断保持关闭
+    pub fn exclusive_access(&self) -> IrqRefMut<'_, T> {
+        push_off();
+        IrqRefMut {
+            inner: ManuallyDrop::new(self.inner.borrow_mut()),
+        }
+    }
+}
+
+// IrqSafeCell的独占访问，释放时先归还借用再恢复中断
+pub struct IrqRefMut<'a, T> {
+    inner: ManuallyDrop<RefMut<'a, T>>,
+}
+
+impl<T> Deref for IrqRefMut<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        &self.inner
+    }
+}
+
+impl<T> DerefMut for IrqRefMut<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+}
+
+impl<T> Drop for IrqRefMut<'_, T> {
+    fn drop(&mut self) {
+        unsafe {
+            ManuallyDrop::drop(&mut self.inner);
+        }
+        pop_off();
+    }
+}
@@ -0,0 +1,67 @@
+// 关中断的自旋锁，多个核共享的内核全局变量都用它保护
+// 先关本核中断再上锁，持锁期间本核的中断处理不会再来抢同一把锁造成死锁
+
+use super::irq::{pop_off, push_off};
+use core::mem::ManuallyDrop;
+use core::ops::{Deref, DerefMut};
+use spin::{Mutex, MutexGuard};
+
+pub struct SpinNoIrq<T> {
+    inner: Mutex<T>,
+}
+
+impl<T> SpinNoIrq<T> {
+    // 新建
+    pub const fn new(value: T) -> Self {
+        Self {
+            inner: Mutex::new(value),
+        }
+    }
+    // 尝试上锁，锁被占用时直接返回None，出错处理中用来避免死锁
+    pub fn try_lock(&self) -> Option<SpinNoIrqGuard<'_, T>> {
+        push_off();
+        match self.inner.try_lock() {
+            Some(guard) => Some(SpinNoIrqGuard {
+                inner: ManuallyDrop::new(guard),
+            }),
+            None => {
+                pop_off();
+                None
+            }
+        }
+    }
+    // 关闭本核中断并上锁，guard释放时解锁并恢复中断
+    pub fn lock(&self) -> SpinNoIrqGuard<'_, T> {
+        push_off();
+        SpinNoIrqGuard {
+            inner: ManuallyDrop::new(self.inner.lock()),
+        }
+    }
+}
+
+// SpinNoIrq的锁，释放时先解锁再恢复中断
+pub struct SpinNoIrqGuard<'a, T> {
+    inner: ManuallyDrop<MutexGuard<'a, T>>,
+}
+
+impl<T> Deref for SpinNoIrqGuard<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        &self.inner
+    }
+}
+
+impl<T> DerefMut for SpinNoIrqGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+}
+
+impl<T> Drop for SpinNoIrqGuard<'_, T> {
+    fn drop(&mut self) {
+        unsafe {
+            ManuallyDrop::drop(&mut self.inner);
+        }
+        pop_off();
+    }
+}
@@ -1,5 +1,9 @@
-// 单线程可安全共享的内部可变RefCell
+// 同步原语：关中断的内部可变单元和自旋锁、等待队列
 
-mod up;
+mod irq;
+mod lock;
+mod wait_queue;
 
-pub use up::UPSafeCell;
+pub use irq::{disable_irq, irq_depth, pop_off, push_off, restore_irq, IrqSafeCell};
+pub use lock::{SpinNoIrq, SpinNoIrqGuard};
+pub use wait_queue::WaitQueue;
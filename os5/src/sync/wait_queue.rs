@@ -0,0 +1,47 @@
+// 等待队列，任务在上面阻塞，等待某个事件发生后被唤醒
+
+use super::SpinNoIrq;
+use crate::task::{block_current_and_run_next, wakeup_task, TaskControlBlock};
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+
+pub struct WaitQueue {
+    // 阻塞在这个队列上的任务
+    waiters: SpinNoIrq<VecDeque<Arc<TaskControlBlock>>>,
+}
+
+impl WaitQueue {
+    pub fn new() -> Self {
+        Self {
+            waiters: SpinNoIrq::new(VecDeque::new()),
+        }
+    }
+    // 当前任务阻塞在这个队列上，被唤醒后返回
+    // 挂上队列之后才释放guard，guard保护的等待条件检查完之后，别的核的唤醒不会被错过
+    pub fn wait_with<G>(&self, guard: G) {
+        block_current_and_run_next(|task| {
+            self.waiters.lock().push_back(task);
+            drop(guard);
+        });
+    }
+    // 唤醒最早阻塞的一个任务，返回是否唤醒了任务
+    pub fn wake_one(&self) -> bool {
+        let task = self.waiters.lock().pop_front();
+        match task {
+            Some(task) => {
+                wakeup_task(task);
+                true
+            }
+            None => false,
+        }
+    }
+    // 唤醒所有任务，返回唤醒的任务数
+    pub fn wake_all(&self) -> usize {
+        let waiters: VecDeque<_> = self.waiters.lock().drain(..).collect();
+        let count = waiters.len();
+        for task in waiters {
+            wakeup_task(task);
+        }
+        count
+    }
+}
@@ -1,19 +1,29 @@
 // 文件读写系统调用，目前只有标准输入和标准输出的读写
 
-use crate::mm::translated_byte_buffer;
+use crate::mm::{translated_byte_buffer, AccessType};
 use crate::sbi::console_getchar;
-use crate::task::{current_user_token, suspend_current_and_run_next};
+use crate::task::{block_current_and_run_next, current_memory_set};
+use crate::timer::{add_timer, get_time_ms};
+use alloc::vec::Vec;
 
 const FD_STDIN: usize = 0;
 const FD_STDOUT: usize = 1;
+// 没有输入时隔多久再查一次串口，单位为毫秒
+const CONSOLE_POLL_MS: usize = 10;
 
 pub fn sys_write(fd: usize, buf: *const u8, len: usize) -> isize {
     match fd {
         FD_STDOUT => {
-            let buffers = translated_byte_buffer(current_user_token(), buf, len);
-            for buffer in buffers {
-                print!("{}", core::str::from_utf8(buffer).unwrap());
+            // 按需分配的页要先分配出来
+            let memory_set = current_memory_set();
+            let mut memory_set = memory_set.lock();
+            if !memory_set.prepare_access(buf as usize, len, AccessType::Read) {
+                return -1;
             }
+            // 先拷贝到内核里，放开地址空间的锁再输出，输出时可以被抢占，共享地址空间的任务也不用等
+            let bytes: Vec<u8> = translated_byte_buffer(memory_set.token(), buf, len).concat();
+            drop(memory_set);
+            print!("{}", core::str::from_utf8(&bytes).unwrap());
             len as isize
         }
         _ => {
@@ -30,14 +40,22 @@ pub fn sys_read(fd: usize, buf: *const u8, len: usize) -> isize {
             loop {
                 c = console_getchar();
                 if c == 0 {
-                    suspend_current_and_run_next();
+                    // 睡眠一会再查，这样空闲时处理器可以进入wfi
+                    let expire_ms = get_time_ms() + CONSOLE_POLL_MS;
+                    block_current_and_run_next(|task| add_timer(expire_ms, task));
                     continue;
                 } else {
                     break;
                 }
             }
             let ch = c as u8;
-            let mut buffers = translated_byte_buffer(current_user_token(), buf, len);
+            // 按需分配和写时复制的页要先准备好
+            let memory_set = current_memory_set();
+            let mut memory_set = memory_set.lock();
+            if !memory_set.prepare_access(buf as usize, len, AccessType::Write) {
+                return -1;
+            }
+            let mut buffers = translated_byte_buffer(memory_set.token(), buf, len);
             unsafe {
                 buffers[0].as_mut_ptr().write_volatile(ch);
             }
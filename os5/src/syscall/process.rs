@@ -1,14 +1,18 @@
 // 进程管理相关的系统调用
 
 use crate::loader::get_app_data_by_name;
-use crate::mm::{translated_refmut, translated_str};
+use crate::mm::{frame_remain_num, shm_get, translated_ref, translated_refmut, translated_str};
+use crate::mm::{AccessType, LoadError, MemorySet};
 use crate::task::{
-    add_task, current_task, current_user_token, exit_current_and_run_next,
-    suspend_current_and_run_next, TaskStatus,
+    add_task, block_current_and_run_next, current_memory_set, current_task,
+    exit_current_and_run_next, suspend_current_and_run_next, update_task_tickets, CloneFlags,
+    TaskStatus,
 };
-use crate::timer::get_time_us;
+use crate::timer::{add_timer, get_time_ms, get_time_us};
+use alloc::string::String;
 use alloc::sync::Arc;
-use crate::config::MAX_SYSCALL_NUM;
+use alloc::vec::Vec;
+use crate::config::{ARG_MAX, KERNEL_STACK_MAX_SIZE, KERNEL_STACK_SIZE, MAX_SYSCALL_NUM, PAGE_SIZE};
 use crate::task::TaskControlBlock;
 
 #[repr(C)]
@@ -38,6 +42,13 @@ pub fn sys_yield() -> isize {
     0
 }
 
+// 睡眠指定的毫秒数，期间阻塞不占用处理器，由时钟中断检查到期后唤醒
+pub fn sys_sleep(sleep_ms: usize) -> isize {
+    let expire_ms = get_time_ms() + sleep_ms;
+    block_current_and_run_next(|task| add_timer(expire_ms, task));
+    0
+}
+
 // 获得pid值
 pub fn sys_getpid() -> isize {
     current_task().unwrap().pid.0 as isize
@@ -47,86 +58,180 @@ pub fn sys_getpid() -> isize {
 pub fn sys_fork() -> isize {
     // 获取当前任务块
     let current_task = current_task().unwrap();
-    // 获取新任务任务块
-    let new_task = current_task.fork();
-    // 获取pid值
-    let new_pid = new_task.pid.0;
-    // 获取新进程trap上下文
-    let trap_cx = new_task.inner_exclusive_access().get_trap_cx();
-    // 修改子进程的寄存器，子进程fork返回0
+    // 获取新任务任务块，子进程的Trap上下文中fork的返回值已经改为0
     // 这样从内核态返回用户态后，父进程和子进程虽然是两个几乎一模一样的平行空间
     // 但是我们可以在用户态的程序里对fork的返回值做判断而执行不同分支，
     // 这样父进程和子进程就可以利用这一点细小的差别走上不相同的道路了
-    trap_cx.x[10] = 0;
+    // 页帧不够复刻不了时返回-1
+    let new_task = match current_task.fork() {
+        Some(new_task) => new_task,
+        None => return -1,
+    };
+    // 获取pid值
+    let new_pid = new_task.pid.0;
     // 压入调度器等待调度
     add_task(new_task);
     new_pid as isize
 }
 
-// 使用elf在进程上运行新内容
-pub fn sys_exec(path: *const u8) -> isize {
-    // 获取地址空间token
-    let token = current_user_token();
-    let path = translated_str(token, path);
-    if let Some(data) = get_app_data_by_name(path.as_str()) {
-        let task = current_task().unwrap();
-        task.exec(data);
-        0
+// 按照flags复制出子进程，CLONE_VM共享地址空间，CLONE_VFORK时等到子进程exec或退出才返回
+// stack不为0时是子进程的用户栈指针，共享地址空间又不vfork时必须给出
+pub fn sys_clone(flags: usize, stack: usize) -> isize {
+    let flags = match CloneFlags::from_bits(flags) {
+        Some(flags) => flags,
+        None => return -1,
+    };
+    // 两个任务不能同时在同一个栈上运行
+    if flags.contains(CloneFlags::CLONE_VM)
+        && !flags.contains(CloneFlags::CLONE_VFORK)
+        && stack == 0
+    {
+        return -1;
+    }
+    let current_task = current_task().unwrap();
+    let new_task = match current_task.clone_task(flags, stack) {
+        Some(new_task) => new_task,
+        None => return -1,
+    };
+    let new_pid = new_task.pid.0;
+    add_task(new_task.clone());
+    if flags.contains(CloneFlags::CLONE_VFORK) {
+        new_task.wait_vfork_done();
+    }
+    new_pid as isize
+}
+
+// 从用户地址空间中取出以0结尾的字符串指针数组，ptr为空指针表示空数组
+// 每个字符串连同结尾的0和数组中的指针计入size，超过ARG_MAX时返回TooBig，访问不了时返回Fault
+fn translated_str_array(
+    memory_set: &mut MemorySet,
+    mut ptr: *const usize,
+    size: &mut usize,
+) -> Result<Vec<String>, SpawnError> {
+    let token = memory_set.token();
+    let mut strs = Vec::new();
+    while !ptr.is_null() {
+        let len = core::mem::size_of::<usize>();
+        if !memory_set.prepare_access(ptr as usize, len, AccessType::Read) {
+            return Err(SpawnError::Fault);
+        }
+        let str_ptr = *translated_ref(token, ptr);
+        if str_ptr == 0 {
+            break;
+        }
+        if !memory_set.prepare_str(str_ptr as *const u8) {
+            return Err(SpawnError::Fault);
+        }
+        let s = translated_str(token, str_ptr as *const u8);
+        *size += s.len() + 1 + core::mem::size_of::<usize>();
+        if *size > ARG_MAX {
+            return Err(SpawnError::TooBig);
+        }
+        strs.push(s);
+        unsafe {
+            ptr = ptr.add(1);
+        }
+    }
+    Ok(strs)
+}
+
+// 使用elf在进程上运行新内容，args为参数，envp为环境变量，envp为空指针时沿用当前的环境变量
+pub fn sys_exec(path: *const u8, args: *const usize, envp: *const usize) -> isize {
+    let task = current_task().unwrap();
+    // 从旧的地址空间中取出路径、参数和环境变量，参数和环境变量合计不能超过ARG_MAX
+    // 用到的页可能还没有分配，先准备好再读
+    let memory_set = current_memory_set();
+    let mut memory_set = memory_set.lock();
+    if !memory_set.prepare_str(path) {
+        return -1;
+    }
+    let path = translated_str(memory_set.token(), path);
+    let mut size = 0;
+    let args_vec = match translated_str_array(&mut memory_set, args, &mut size) {
+        Ok(args_vec) => args_vec,
+        Err(_) => return -1,
+    };
+    let envs = if envp.is_null() {
+        None
     } else {
-        -1
+        match translated_str_array(&mut memory_set, envp, &mut size) {
+            Ok(envs) => Some(envs),
+            Err(_) => return -1,
+        }
+    };
+    // 先放开地址空间的锁，任务块的锁要先加
+    drop(memory_set);
+    let envs = envs.unwrap_or_else(|| task.inner_exclusive_access().envs.clone());
+    match get_app_data_by_name(path.as_str()) {
+        Some(data) if task.exec(data, args_vec, envs) => 0,
+        _ => -1,
     }
 }
 
 
-// sys_waitpid 是一个立即返回的系统调用，它的返回值语义是：
+// sys_waitpid 的返回值语义是：
 // 如果当前的进程不存在一个进程 ID 为 pid（pid==-1 或 pid > 0）的子进程，则返回 -1；
 // 如果存在一个进程 ID 为 pid 的僵尸子进程，则正常回收并返回子进程的 pid，并更新系统调用的退出码参数为 exit_code 。
-// 这里还有一个 -2 的返回值，它的含义是子进程还没退出，通知用户库 user_lib （是实际发出系统调用的地方），
-// 这样用户库看到是 -2 后，就进一步调用 sys_yield 系统调用（第46行），让当前父进程进入等待状态。
+// 如果子进程还没退出，当前进程阻塞在自己的 child_exit_queue 上，直到有子进程退出时被唤醒再重新检查，
+// 这样父进程等待期间不会再占用时间片。
 pub fn sys_waitpid(pid: isize, exit_code_ptr: *mut i32) -> isize {
     // 获取当前任务控制块
     let task = current_task().unwrap();
-    // 寻找子进程
+    loop {
+        // 寻找子进程
 
-    // ---- 获取任务块可变访问
-    let mut inner = task.inner_exclusive_access();
-    // 如果要等待的子进程不存在则返回 -1
-    if !inner
-        .children
-        .iter()
-        .any(|p| pid == -1 || pid as usize == p.getpid())
-    {
-        return -1;
-        // ---- 释放任务块可变访问
-    }
+        // ---- 获取任务块可变访问
+        let mut inner = task.inner_exclusive_access();
+        // 如果要等待的子进程不存在则返回 -1
+        if !inner
+            .children
+            .iter()
+            .any(|p| pid == -1 || pid as usize == p.getpid())
+        {
+            return -1;
+            // ---- 释放任务块可变访问
+        }
 
-    // 等待的子进程存在,看看是不是僵尸进程
-    let pair = inner.children.iter().enumerate().find(|(_, p)| {
-        // ++++ 获取子进程的访问
-        p.inner_exclusive_access().is_zombie() && (pid == -1 || pid as usize == p.getpid())
-        // ++++ 释放访问
-    });
-    if let Some((idx, _)) = pair {
-        let child = inner.children.remove(idx);
-        // 确认这是对于该子进程控制块的唯一一次强引用
-        assert_eq!(Arc::strong_count(&child), 1);
-        let found_pid = child.getpid();
-        // ++++ 获取子进程的访问
-        let exit_code = child.inner_exclusive_access().exit_code;
-        // ++++ 释放访问
-        *translated_refmut(inner.memory_set.token(), exit_code_ptr) = exit_code;
-        found_pid as isize
-    // 不是僵尸进程就返回-2
-    } else {
-        -2
+        // 等待的子进程存在,看看是不是僵尸进程
+        let pair = inner.children.iter().enumerate().find(|(_, p)| {
+            // ++++ 获取子进程的访问
+            p.inner_exclusive_access().is_zombie() && (pid == -1 || pid as usize == p.getpid())
+            // ++++ 释放访问
+        });
+        if let Some((idx, _)) = pair {
+            // ++++ 获取子进程的访问
+            let exit_code = inner.children[idx].inner_exclusive_access().exit_code;
+            // ++++ 释放访问
+            // 按需分配、写时复制和换出了的页要先准备好，写不了就不回收子进程
+            // 准备好的页被钉住，写完之前又一直锁着地址空间，不会被换出
+            let mut memory_set = inner.memory_set.lock();
+            let len = core::mem::size_of::<i32>();
+            if !memory_set.prepare_access(exit_code_ptr as usize, len, AccessType::Write) {
+                return -1;
+            }
+            *translated_refmut(memory_set.token(), exit_code_ptr) = exit_code;
+            drop(memory_set);
+            // 子进程所在核的空闲控制流可能还短暂持有引用，最后一个引用释放时才会回收
+            let child = inner.children.remove(idx);
+            return child.getpid() as isize;
+        }
+        // 不是僵尸进程就阻塞，等子进程退出时唤醒
+        // 挂上等待队列之后才释放任务块，子进程在别的核上退出时不会错过唤醒
+        task.child_exit_queue.wait_with(inner);
+        // ---- 释放任务块可变访问
     }
-    // ---- 释放任务块可变访问
 }
 
 // YOUR JOB: 引入虚地址后重写 sys_get_time
 pub fn sys_get_time(ts: *mut TimeVal, _tz: usize) -> isize {
     let us = get_time_us();
-    let ts_va = translated_refmut(current_user_token(), ts);
+    let memory_set = current_memory_set();
+    let mut memory_set = memory_set.lock();
+    let len = core::mem::size_of::<TimeVal>();
+    if !memory_set.prepare_access(ts as usize, len, AccessType::Write) {
+        return -1;
+    }
+    let ts_va = translated_refmut(memory_set.token(), ts);
     *ts_va = TimeVal {
         sec: us / 1_000_000,
         usec: us % 1_000_000,
@@ -136,9 +241,14 @@ pub fn sys_get_time(ts: *mut TimeVal, _tz: usize) -> isize {
 
 // YOUR JOB: 引入虚地址后重写 sys_task_info
 pub fn sys_task_info(ti: *mut TaskInfo) -> isize {
-    let ti_va = translated_refmut(current_user_token(), ti);
     let current_task = current_task().unwrap();
     let ctcb = current_task.inner_exclusive_access();
+    let mut memory_set = ctcb.memory_set.lock();
+    let len = core::mem::size_of::<TaskInfo>();
+    if !memory_set.prepare_access(ti as usize, len, AccessType::Write) {
+        return -1;
+    }
+    let ti_va = translated_refmut(memory_set.token(), ti);
     *ti_va = TaskInfo {
         status: ctcb.task_status,
         syscall_times: ctcb.task_syscall_times,
@@ -155,12 +265,51 @@ pub fn sys_set_priority(prio: isize) -> isize {
         .set_task_priority(prio)
 }
 
+// 设置彩票调度的彩票数
+pub fn sys_set_tickets(tickets: isize) -> isize {
+    let task = current_task().unwrap();
+    let result = task.inner_exclusive_access().set_task_tickets(tickets);
+    if result > 0 {
+        update_task_tickets(&task, result as usize);
+    }
+    result
+}
+
+// 把自己的一部分彩票借给子进程，子进程退出时归还，自己至少要留下一张
+// 成功返回子进程现在的彩票数，子进程不存在或彩票不够返回-1
+pub fn sys_transfer_tickets(pid: usize, tickets: usize) -> isize {
+    let task = current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    if tickets == 0 || tickets >= inner.task_tickets {
+        return -1;
+    }
+    let child = match inner.children.iter().find(|child| child.getpid() == pid) {
+        Some(child) => child.clone(),
+        None => return -1,
+    };
+    let mut child_inner = child.inner_exclusive_access();
+    if child_inner.is_zombie() {
+        return -1;
+    }
+    inner.task_tickets -= tickets;
+    child_inner.task_tickets += tickets;
+    child_inner.task_borrowed_tickets += tickets;
+    let (parent_tickets, child_tickets) = (inner.task_tickets, child_inner.task_tickets);
+    drop(child_inner);
+    drop(inner);
+    // 放开两边的锁之后再通知调度器
+    update_task_tickets(&task, parent_tickets);
+    update_task_tickets(&child, child_tickets);
+    child_tickets as isize
+}
+
 // YOUR JOB: 扩展内核以实现 sys_mmap 和 sys_munmap
 pub fn sys_mmap(start: usize, len: usize, port: usize) -> isize {
     current_task()
         .unwrap()
         .inner_exclusive_access()
         .memory_set
+        .lock()
         .mmap(start, len, port)
 }
 
@@ -169,28 +318,181 @@ pub fn sys_munmap(start: usize, len: usize) -> isize {
         .unwrap()
         .inner_exclusive_access()
         .memory_set
+        .lock()
         .munmap(start, len)
 }
 
+// 修改[start, start + len)的访问权限，port的含义和sys_mmap一样
+pub fn sys_mprotect(start: usize, len: usize, port: usize) -> isize {
+    current_memory_set().lock().mprotect(start, len, port)
+}
+
+// 打开键值为key、长度为len字节的共享内存对象，映射到由内核选的地址并返回这个地址，
+// port的含义和sys_mmap一样
+// key为0时总是新建一个只能通过fork共享的对象，已有的对象长度不同时失败
+pub fn sys_shm_open(key: usize, len: usize, port: usize) -> isize {
+    let pages = len / PAGE_SIZE + (len % PAGE_SIZE != 0) as usize;
+    if pages == 0 || pages > frame_remain_num() {
+        return -1;
+    }
+    match shm_get(key, pages) {
+        Some(shm) => current_memory_set().lock().mmap_shared(shm, port),
+        None => -1,
+    }
+}
+
+// 调整用户堆的结束地址，addr为0时只查询，返回调整后的结束地址，调整不了时返回原来的
+pub fn sys_brk(addr: usize) -> isize {
+    current_memory_set().lock().brk(addr) as isize
+}
+
+// spawn的属性中各个字段是否有效
+pub const SPAWN_SETPRIORITY: usize = 1 << 0;
+pub const SPAWN_SETTICKETS: usize = 1 << 1;
+pub const SPAWN_SETKSTACK: usize = 1 << 2;
+pub const SPAWN_SETENV: usize = 1 << 3;
+pub const SPAWN_SETSTACK: usize = 1 << 4;
+const SPAWN_FLAGS: usize =
+    SPAWN_SETPRIORITY | SPAWN_SETTICKETS | SPAWN_SETKSTACK | SPAWN_SETENV | SPAWN_SETSTACK;
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+// spawn的可选属性，flags中对应的位置位时相应的字段才有效，否则取默认值
+pub struct SpawnAttr {
+    pub flags: usize,
+    // 初始优先级，至少为2，默认16
+    pub priority: isize,
+    // 初始彩票数，至少为1，默认DEFAULT_TICKETS
+    pub tickets: isize,
+    // 内核栈大小，按页对齐且不超过KERNEL_STACK_MAX_SIZE，默认KERNEL_STACK_SIZE
+    pub kernel_stack_size: usize,
+    // 以0结尾的环境变量指针数组，默认继承父进程的环境变量
+    pub envp: usize,
+    // 用户栈最多能增长到的大小，按页对齐且不小于USER_STACK_SIZE，默认USER_STACK_LIMIT
+    pub stack_limit: usize,
+}
+
+#[repr(isize)]
+#[derive(Clone, Copy)]
+// spawn失败的原因，以负数返回给用户，取值是Linux中对应errno的相反数
+pub enum SpawnError {
+    // 没有这个名字的应用，ENOENT
+    NotFound = -2,
+    // 参数和环境变量超过了ARG_MAX，E2BIG
+    TooBig = -7,
+    // 应用装载不了，ENOEXEC
+    NoExec = -8,
+    // 页帧不够，ENOMEM
+    OutOfMemory = -12,
+    // 路径、参数或属性所在的用户内存访问不了，EFAULT
+    Fault = -14,
+    // 属性不合法，EINVAL
+    Invalid = -22,
+}
+
+impl From<LoadError> for SpawnError {
+    fn from(err: LoadError) -> Self {
+        match err {
+            LoadError::BadElf => SpawnError::NoExec,
+            LoadError::NoMemory => SpawnError::OutOfMemory,
+        }
+    }
+}
 
 // YOUR JOB: 实现 sys_spawn 系统调用
 // ALERT: 注意在实现 SPAWN 时不需要复制父进程地址空间，SPAWN != FORK + EXEC 
-pub fn sys_spawn(path: *const u8) -> isize {
-    let token = current_user_token();
+// 新建子进程运行path指定的应用，不复制父进程的地址空间
+// args为以0结尾的参数指针数组，attr为空指针时全部取默认值，成功返回子进程的pid
+pub fn sys_spawn(path: *const u8, args: *const usize, attr: *const SpawnAttr) -> isize {
+    match spawn(path, args, attr) {
+        Ok(pid) => pid as isize,
+        Err(err) => err as isize,
+    }
+}
+
+fn spawn(path: *const u8, args: *const usize, attr: *const SpawnAttr) -> Result<usize, SpawnError> {
+    // 用到的页可能还没有分配，先准备好再读
+    let memory_set = current_memory_set();
+    let mut memory_set = memory_set.lock();
+    let token = memory_set.token();
+    if !memory_set.prepare_str(path) {
+        return Err(SpawnError::Fault);
+    }
     let path = translated_str(token, path);
-    if let Some(data) = get_app_data_by_name(path.as_str()) {
-        let new_task = Arc::new(TaskControlBlock::new(data));
-        let mut new_inner = new_task.inner_exclusive_access();
-        let parent = current_task().unwrap();
-        let mut parent_inner = parent.inner_exclusive_access();
-        new_inner.parent = Some(Arc::downgrade(&parent));
-        parent_inner.children.push(new_task.clone());
-        let pid = new_task.pid.0 as isize;
-        drop(new_inner);
-        drop(parent_inner);
-        add_task(new_task);
-        pid
+    let attr = if attr.is_null() {
+        SpawnAttr {
+            flags: 0,
+            priority: 0,
+            tickets: 0,
+            kernel_stack_size: 0,
+            envp: 0,
+            stack_limit: 0,
+        }
     } else {
-        -1
+        let len = core::mem::size_of::<SpawnAttr>();
+        if !memory_set.prepare_access(attr as usize, len, AccessType::Read) {
+            return Err(SpawnError::Fault);
+        }
+        *translated_ref(token, attr)
+    };
+    // 先检查属性
+    if attr.flags & !SPAWN_FLAGS != 0
+        || (attr.flags & SPAWN_SETPRIORITY != 0 && attr.priority < 2)
+        || (attr.flags & SPAWN_SETTICKETS != 0 && attr.tickets < 1)
+    {
+        return Err(SpawnError::Invalid);
+    }
+    let kernel_stack_size = if attr.flags & SPAWN_SETKSTACK != 0 {
+        let size = attr.kernel_stack_size;
+        if size == 0 || size > KERNEL_STACK_MAX_SIZE || size % PAGE_SIZE != 0 {
+            return Err(SpawnError::Invalid);
+        }
+        size
+    } else {
+        KERNEL_STACK_SIZE
+    };
+    let data = get_app_data_by_name(path.as_str()).ok_or(SpawnError::NotFound)?;
+    let parent = current_task().unwrap();
+    // 取出参数和环境变量，两者合计不能超过ARG_MAX
+    let mut size = 0;
+    let args = translated_str_array(&mut memory_set, args, &mut size)?;
+    let envs = if attr.flags & SPAWN_SETENV != 0 {
+        Some(translated_str_array(
+            &mut memory_set,
+            attr.envp as *const usize,
+            &mut size,
+        )?)
+    } else {
+        None
+    };
+    // 先放开地址空间的锁，任务块的锁要先加
+    drop(memory_set);
+    let envs = envs.unwrap_or_else(|| parent.inner_exclusive_access().envs.clone());
+    let new_task = Arc::new(TaskControlBlock::new(data, kernel_stack_size, args, envs)?);
+    // 用户栈的大小要在地址空间建好之后才能检查
+    if attr.flags & SPAWN_SETSTACK != 0
+        && !new_task
+            .inner_exclusive_access()
+            .memory_set
+            .lock()
+            .set_stack_limit(attr.stack_limit)
+    {
+        return Err(SpawnError::Invalid);
+    }
+    // 先父后子加锁
+    let mut parent_inner = parent.inner_exclusive_access();
+    let mut new_inner = new_task.inner_exclusive_access();
+    new_inner.parent = Some(Arc::downgrade(&parent));
+    if attr.flags & SPAWN_SETPRIORITY != 0 {
+        new_inner.set_task_priority(attr.priority);
     }
+    if attr.flags & SPAWN_SETTICKETS != 0 {
+        new_inner.set_task_tickets(attr.tickets);
+    }
+    parent_inner.children.push(new_task.clone());
+    let pid = new_task.getpid();
+    drop(new_inner);
+    drop(parent_inner);
+    add_task(new_task);
+    Ok(pid)
 }
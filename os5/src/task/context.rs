@@ -2,7 +2,7 @@
 
 use crate::trap::trap_return;
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug)]
 #[repr(C)]
 // 任务切换瞬间的快照，任务上下文
 pub struct TaskContext {
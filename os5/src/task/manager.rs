@@ -1,76 +1,89 @@
 // 进程调度器模块
 
 
+use super::scheduler::{SchedPolicy, Scheduler};
 use super::TaskControlBlock;
-use crate::sync::UPSafeCell;
-use alloc::collections::VecDeque;
+use crate::sync::SpinNoIrq;
+use alloc::boxed::Box;
 use alloc::sync::Arc;
 use lazy_static::*;
 
-// 进程调度器
+// 进程调度器，具体的调度策略交给可插拔的Scheduler
 pub struct TaskManager {
-    // 挂起进程的序列，双端队列
-    ready_queue: VecDeque<Arc<TaskControlBlock>>,
+    scheduler: Box<dyn Scheduler>,
 }
 
-// YOUR JOB: FIFO->Stride
-// 采用Stride调度模型，进程按优先级对应的步长增加长度
-// 每次取用长度最短的进程
 impl TaskManager {
-    // 新建调度器
+    // 新建调度器，默认使用Stride调度
     pub fn new() -> Self {
         Self {
-            ready_queue: VecDeque::new(),
+            scheduler: SchedPolicy::Stride.build(),
         }
     }
+    // 更换调度策略
+    pub fn set_policy(&mut self, policy: SchedPolicy) {
+        self.scheduler = policy.build();
+    }
+    // 当前调度策略名称
+    pub fn policy_name(&self) -> &'static str {
+        self.scheduler.name()
+    }
     // 将任务压回待调度队列
     pub fn add(&mut self, task: Arc<TaskControlBlock>) {
-        self.ready_queue.push_back(task);
+        self.scheduler.add(task);
     }
-    // 从待调度队列弹出最前端的任务
+    // 从待调度队列按调度策略弹出任务
     pub fn fetch(&mut self) -> Option<Arc<TaskControlBlock>> {
-        let mut min_pass: usize = core::usize::MAX;
-        let mut min_pass_index: Option<usize> = None;
-        for index in 0..self.ready_queue.len() {
-            let index_pass = self.ready_queue[index].inner_exclusive_access().task_pass;
-            if index_pass <= min_pass {
-                min_pass = index_pass;
-                min_pass_index = Some(index);
-            }
-        }
-        self.ready_queue.swap_remove_back(min_pass_index.unwrap())
+        self.scheduler.fetch()
     }
 }
-// // 采用FIFO调度模型，无优先级，循环排队调度
-// impl TaskManager {
-//     // 新建调度器
-//     pub fn new() -> Self {
-//         Self {
-//             ready_queue: VecDeque::new(),
-//         }
-//     }
-//     // 将任务压回待调度队列
-//     pub fn add(&mut self, task: Arc<TaskControlBlock>) {
-//         self.ready_queue.push_back(task);
-//     }
-//     // 从待调度队列弹出最前端的任务
-//     pub fn fetch(&mut self) -> Option<Arc<TaskControlBlock>> {
-//         self.ready_queue.pop_front()
-//     }
-// }
 
 lazy_static! {
-    // 初始化调度器
-    pub static ref TASK_MANAGER: UPSafeCell<TaskManager> =
-        unsafe { UPSafeCell::new(TaskManager::new()) };
+    // 初始化调度器，所有核共享一个就绪队列，用自旋锁保护
+    pub static ref TASK_MANAGER: SpinNoIrq<TaskManager> = SpinNoIrq::new(TaskManager::new());
 }
 
 // 接口，任务压回调度器
 pub fn add_task(task: Arc<TaskControlBlock>) {
-    TASK_MANAGER.exclusive_access().add(task);
+    TASK_MANAGER.lock().add(task);
 }
 
 // 接口，从调度器取一个任务
 pub fn fetch_task() -> Option<Arc<TaskControlBlock>> {
-    TASK_MANAGER.exclusive_access().fetch()
+    TASK_MANAGER.lock().fetch()
+}
+
+// 接口，通知调度器当前任务经历了一次时钟中断，返回是否需要抢占
+pub fn tick_task(task: &Arc<TaskControlBlock>) -> bool {
+    TASK_MANAGER.lock().scheduler.on_tick(task)
+}
+
+// 接口，通知调度器当前任务主动让出处理器
+pub fn yield_task(task: &Arc<TaskControlBlock>) {
+    TASK_MANAGER.lock().scheduler.on_yield(task);
+}
+
+// 接口，通知调度器当前任务退出
+pub fn exit_task(task: &Arc<TaskControlBlock>) {
+    TASK_MANAGER.lock().scheduler.on_exit(task);
+}
+
+// 接口，通知调度器任务的彩票数变了，调用者不能持有这个任务的锁
+pub fn update_task_tickets(task: &Arc<TaskControlBlock>, tickets: usize) {
+    TASK_MANAGER.lock().scheduler.set_tickets(task, tickets);
+}
+
+// 接口，当前调度策略名称
+pub fn scheduler_name() -> &'static str {
+    TASK_MANAGER.lock().policy_name()
+}
+
+// 接口，启动时选择调度策略，必须在任何任务加入之前调用
+pub fn init_scheduler(name: &str) {
+    let mut manager = TASK_MANAGER.lock();
+    match SchedPolicy::from_name(name) {
+        Some(policy) => manager.set_policy(policy),
+        None => warn!("unknown scheduler {:?}, using {}", name, manager.policy_name()),
+    }
+    println!("[kernel] scheduler: {}", manager.policy_name());
 }
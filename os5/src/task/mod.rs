@@ -4,37 +4,90 @@ mod context;
 mod manager;
 mod pid;
 mod processor;
+mod scheduler;
 mod switch;
 #[allow(clippy::module_inception)]
 mod task;
 
+use crate::config::{INIT_ENV, KERNEL_STACK_SIZE};
 use crate::loader::get_app_data_by_name;
-use crate::config::BIG_STRIDE;
+use crate::mm::frame_remain_num;
+use crate::sbi::shutdown;
+use crate::sync::{disable_irq, restore_irq};
+use crate::timer::get_time_ms;
+use alloc::format;
+use alloc::string::String;
 use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::iter::once;
 use lazy_static::*;
-use manager::fetch_task;
+use manager::{exit_task, fetch_task, scheduler_name, tick_task, yield_task};
 use switch::__switch;
-pub use task::{TaskControlBlock, TaskStatus};
+pub use task::{CloneFlags, TaskControlBlock, TaskStatus};
 
 pub use context::TaskContext;
-pub use manager::add_task;
-pub use pid::{pid_alloc, KernelStack, PidHandle};
+pub use manager::{add_task, init_scheduler, update_task_tickets};
+pub use pid::{kernel_stack_overflow_slot, pid_alloc, KernelStack, PidHandle};
 pub use processor::{
-    current_task, current_trap_cx, current_user_token, run_tasks, schedule, take_current_task,
+    current_memory_set, current_task, current_trap_cx, current_trap_cx_user_va,
+    current_user_token, hart_id, hart_online, online_hart_mask, online_harts, run_tasks, schedule,
+    take_current_task,
 };
 
-// 挂起当前进程，运行下一个进程
+// 挂起当前进程，运行下一个进程（主动让出）
 pub fn suspend_current_and_run_next() {
     // 获取当前进程的任务控制块
     let task = take_current_task().unwrap();
+    // 通知调度策略
+    yield_task(&task);
+    requeue_and_run_next(task);
+}
+
+// 时钟中断时调用，由调度策略决定是否抢占当前进程
+pub fn preempt_current_and_run_next() {
+    let task = current_task().unwrap();
+    if !tick_task(&task) {
+        return;
+    }
+    drop(task);
+    requeue_and_run_next(take_current_task().unwrap());
+}
+
+// 阻塞当前进程，不压回调度器，切换到空闲上下文，之后需要由wakeup_task唤醒
+// publish负责把当前进程挂到某个等待队列或者定时器上，
+// 从挂上去到切换走之间关闭中断，否则被时钟中断抢占会把它压回调度器，唤醒时又压一次
+pub fn block_current_and_run_next(publish: impl FnOnce(Arc<TaskControlBlock>)) {
+    let sie = disable_irq();
+    publish(current_task().unwrap());
+    let task = take_current_task().unwrap();
+    // 通知调度策略
+    yield_task(&task);
+    let mut task_inner = task.inner_exclusive_access();
+    let task_cx_ptr = &mut task_inner.task_cx as *mut TaskContext;
+    // 切换到阻塞状态
+    task_inner.task_status = TaskStatus::Blocked;
+    drop(task_inner);
+    // 控制块由等待队列持有
+    drop(task);
+    schedule(task_cx_ptr);
+    restore_irq(sie);
+}
+
+// 唤醒阻塞的进程，压回调度器
+pub fn wakeup_task(task: Arc<TaskControlBlock>) {
+    let mut task_inner = task.inner_exclusive_access();
+    task_inner.task_status = TaskStatus::Ready;
+    drop(task_inner);
+    add_task(task);
+}
 
+// 将让出处理器的进程压回调度器，切换到空闲上下文
+fn requeue_and_run_next(task: Arc<TaskControlBlock>) {
     // 独占地访问任务控制块
     let mut task_inner = task.inner_exclusive_access();
     let task_cx_ptr = &mut task_inner.task_cx as *mut TaskContext;
     // 切换到挂起状态
     task_inner.task_status = TaskStatus::Ready;
-    // 更新长度
-    task_inner.task_pass += BIG_STRIDE / task_inner.task_priority;
     // 手动释放
     drop(task_inner);
 
@@ -48,31 +101,85 @@ pub fn suspend_current_and_run_next() {
 pub fn exit_current_and_run_next(exit_code: i32) {
     // 直接获取任务控制块的本体，因为一会就要杀掉进程了
     let task = take_current_task().unwrap();
+    // 用户初始进程退出，整个系统也就结束了，带着它的退出码关机
+    if Arc::ptr_eq(&task, &INITPROC) {
+        println!("[kernel] initproc exited with code {}, shutting down", exit_code);
+        println!(
+            "[kernel] uptime {} ms, {} free physical frames",
+            get_time_ms(),
+            frame_remain_num()
+        );
+        shutdown(exit_code);
+    }
+    // 通知调度策略
+    exit_task(&task);
     // **** 访问内部可变部分
     let mut inner = task.inner_exclusive_access();
     // 变僵尸
     inner.task_status = TaskStatus::Zombie;
     // 记录退出码
     inner.exit_code = exit_code;
+    // 归还从父进程借来的彩票
+    let borrowed_tickets = inner.task_borrowed_tickets;
+    inner.task_tickets = inner.task_tickets.saturating_sub(borrowed_tickets);
+    inner.task_borrowed_tickets = 0;
+    let parent = inner.parent.as_ref().and_then(|parent| parent.upgrade());
+    // 取走子进程列表
+    let children = core::mem::take(&mut inner.children);
+    // 释放地址空间，和别的任务共享时只还回自己的Trap上下文页
+    let trap_cx_va = inner.trap_cx_va;
+    let mut memory_set = inner.memory_set.lock();
+    if memory_set.is_shared() {
+        memory_set.dealloc_trap_cx(trap_cx_va);
+    } else {
+        memory_set.recycle_data_pages();
+    }
+    memory_set.detach_task();
+    drop(memory_set);
+    // vfork的父进程可以继续运行了
+    let vfork_pending = core::mem::replace(&mut inner.vfork_pending, false);
+    drop(inner);
+    // **** 释放内部可变部分
+    if vfork_pending {
+        task.vfork_done.wake_all();
+    }
+    // 之后要锁父进程和用户初始进程，必须先放开自己，否则会和waitpid中先父后子的加锁顺序死锁
+
+    if let Some(parent) = parent {
+        // 父进程在waitpid中检查子进程和挂上等待队列都持有自己的锁，
+        // 这里即使没有彩票要还也要锁一次，保证下面的唤醒不会落在两者之间
+        let mut parent_inner = parent.inner_exclusive_access();
+        parent_inner.task_tickets += borrowed_tickets;
+        let parent_tickets = parent_inner.task_tickets;
+        drop(parent_inner);
+        if borrowed_tickets > 0 {
+            update_task_tickets(&parent, parent_tickets);
+        }
+        // 唤醒等待子进程退出的父进程
+        parent.child_exit_queue.wake_all();
+    }
     // do not move to its parent but under initproc
 
     // ++++++ 访问用户初始程序的任务控制块
+    let mut has_zombie_child = false;
     {
         let mut initproc_inner = INITPROC.inner_exclusive_access();
         // 移交子进程给用户初始进程
-        for child in inner.children.iter() {
-            child.inner_exclusive_access().parent = Some(Arc::downgrade(&INITPROC));
-            initproc_inner.children.push(child.clone());
+        for child in children {
+            let mut child_inner = child.inner_exclusive_access();
+            child_inner.parent = Some(Arc::downgrade(&INITPROC));
+            has_zombie_child |= child_inner.is_zombie();
+            drop(child_inner);
+            initproc_inner.children.push(child);
         }
     }
     // ++++++ 释放用户初始程序的任务控制块
+    // 移交了已退出的子进程，用户初始进程可能在等待
+    if has_zombie_child {
+        INITPROC.child_exit_queue.wake_all();
+    }
 
-    inner.children.clear();
-    // 释放地址空间
-    inner.memory_set.recycle_data_pages();
-    drop(inner);
-    // **** 释放内部可变部分
-    // 释放任务控制块本体
+    // 释放任务控制块本体，这个核的空闲控制流还持有引用，切换走之后才会真正回收内核栈
     drop(task);
     // 不需要保存上下文，直接搞个unused就好
     let mut _unused = TaskContext::zero_init();
@@ -84,8 +191,14 @@ lazy_static! {
     // 用户初始程序，创建任务控制块
     pub static ref INITPROC: Arc<TaskControlBlock> = Arc::new(TaskControlBlock::new(
         // 通过应用名取出对应的应用的ELF，用于构建任务控制块
-        get_app_data_by_name("ch5b_initproc").unwrap()
-    ));
+        get_app_data_by_name("ch5b_initproc").unwrap(),
+        KERNEL_STACK_SIZE,
+        Vec::new(),
+        // 当前调度策略放在SCHED中，依赖调度策略的测试据此决定是否运行；后面是编译时给定的环境变量
+        once(format!("SCHED={}", scheduler_name()))
+            .chain(INIT_ENV.split_whitespace().map(String::from))
+            .collect(),
+    ).unwrap());
 }
 
 // 被main函数调用，启动用户初始程序
@@ -1,15 +1,17 @@
-// pid实现。进程的唯一标识符，同时唯一标识进程的内核栈
+// pid实现。进程的唯一标识符；以及内核栈，内核栈按单独分配的槽位放置，和pid无关
 
-use crate::config::{KERNEL_STACK_SIZE, PAGE_SIZE, TRAMPOLINE};
+use crate::config::{KERNEL_STACK_MAX_SIZE, PAGE_SIZE, PID_MAX, TRAMPOLINE};
 use crate::mm::{MapPermission, VirtAddr, KERNEL_SPACE};
-use crate::sync::UPSafeCell;
+use crate::sync::SpinNoIrq;
+use alloc::collections::BTreeSet;
 use alloc::vec::Vec;
 use lazy_static::*;
 
 // pid作为一种资源，也使用分配器和自动回收
+// pid单调递增，到PID_MAX后回绕，跳过还在使用的，刚退出的进程的pid不会马上被新进程拿到
 struct PidAllocator {
-    current: usize, // 新分配pid，自增即可
-    recycled: Vec<usize>, // 回收的pid
+    current: usize, // 下一个尝试分配的pid
+    in_use: BTreeSet<usize>, // 正在使用的pid
 }
 
 // pid分配器的方法
@@ -18,37 +20,31 @@ impl PidAllocator {
     pub fn new() -> Self {
         PidAllocator {
             current: 0,
-            recycled: Vec::new(),
+            in_use: BTreeSet::new(),
         }
     }
     // 分配pid句柄
     pub fn alloc(&mut self) -> PidHandle {
-        // 用二手的
-        if let Some(pid) = self.recycled.pop() {
-            PidHandle(pid)
-        // 用全新的
-        } else {
-            self.current += 1;
-            PidHandle(self.current - 1)
+        assert!(self.in_use.len() < PID_MAX, "no free pid");
+        // 找到下一个没有在用的pid
+        while self.in_use.contains(&self.current) {
+            self.current = (self.current + 1) % PID_MAX;
         }
+        let pid = self.current;
+        self.current = (self.current + 1) % PID_MAX;
+        self.in_use.insert(pid);
+        PidHandle(pid)
     }
     // 回收pid句柄
     pub fn dealloc(&mut self, pid: usize) {
         // 检查是否是未分配出去的
-        assert!(pid < self.current);
-        assert!(
-            !self.recycled.iter().any(|ppid| *ppid == pid),
-            "pid {} has been deallocated!",
-            pid
-        );
-        self.recycled.push(pid);
+        assert!(self.in_use.remove(&pid), "pid {} has been deallocated!", pid);
     }
 }
 
 lazy_static! {
-    // 定义一个pid分配器
-    static ref PID_ALLOCATOR: UPSafeCell<PidAllocator> =
-        unsafe { UPSafeCell::new(PidAllocator::new()) };
+    // 定义一个pid分配器，多个核共享，用自旋锁保护
+    static ref PID_ALLOCATOR: SpinNoIrq<PidAllocator> = SpinNoIrq::new(PidAllocator::new());
 }
 
 // pid的资源抽象，也就是pid句柄
@@ -58,43 +54,109 @@ pub struct PidHandle(pub usize);
 impl Drop for PidHandle {
     fn drop(&mut self) {
         //println!("drop pid {}", self.0);
-        PID_ALLOCATOR.exclusive_access().dealloc(self.0);
+        PID_ALLOCATOR.lock().dealloc(self.0);
     }
 }
 
 // 接口，分配pid句柄
 pub fn pid_alloc() -> PidHandle {
-    PID_ALLOCATOR.exclusive_access().alloc()
+    PID_ALLOCATOR.lock().alloc()
+}
+
+// 内核栈槽位的编号分配器，和pid无关，回收的编号可以马上重用，让内核栈区域保持紧凑
+struct KernelStackAllocator {
+    current: usize, // 新分配的编号，自增即可
+    recycled: Vec<usize>, // 回收的编号
+}
+
+impl KernelStackAllocator {
+    pub fn new() -> Self {
+        Self {
+            current: 0,
+            recycled: Vec::new(),
+        }
+    }
+    // 分配槽位编号
+    pub fn alloc(&mut self) -> usize {
+        // 用二手的
+        if let Some(slot) = self.recycled.pop() {
+            slot
+        // 用全新的
+        } else {
+            self.current += 1;
+            self.current - 1
+        }
+    }
+    // 回收槽位编号
+    pub fn dealloc(&mut self, slot: usize) {
+        assert!(slot < self.current);
+        assert!(
+            !self.recycled.iter().any(|s| *s == slot),
+            "kernel stack slot {} has been deallocated!",
+            slot
+        );
+        self.recycled.push(slot);
+    }
 }
 
-// 通过pid查询进程的内核栈栈顶和栈底应该分配在哪，内核栈是根据进程id进行从上到下按序按需线性分配的
-pub fn kernel_stack_position(app_id: usize) -> (usize, usize) {
-    let top = TRAMPOLINE - app_id * (KERNEL_STACK_SIZE + PAGE_SIZE);
-    let bottom = top - KERNEL_STACK_SIZE;
+lazy_static! {
+    // 内核栈槽位分配器
+    static ref KSTACK_ALLOCATOR: SpinNoIrq<KernelStackAllocator> =
+        SpinNoIrq::new(KernelStackAllocator::new());
+}
+
+// 每个内核栈槽位的大小，最大的内核栈加一页保护页
+const KERNEL_STACK_SLOT_SIZE: usize = KERNEL_STACK_MAX_SIZE + PAGE_SIZE;
+// Sv39高半部分的起始地址，内核栈都在这之上
+const KERNEL_STACK_AREA_START: usize = !((1 << 38) - 1);
+
+// 查询槽位中的内核栈栈顶和栈底应该分配在哪，槽位从跳板页往下按编号线性排列
+// 栈从槽位顶端向下映射size大小，槽位中下面没有映射的部分都是保护区
+pub fn kernel_stack_position(slot: usize, size: usize) -> (usize, usize) {
+    let top = TRAMPOLINE - slot * KERNEL_STACK_SLOT_SIZE;
+    let bottom = top - size;
     (bottom, top)
 }
 
+// 访问出错的地址落在哪个内核栈槽位中，内核栈已映射的部分不会访问出错，所以就是落在了它的保护区里
+pub fn kernel_stack_overflow_slot(addr: usize) -> Option<usize> {
+    if (KERNEL_STACK_AREA_START..TRAMPOLINE).contains(&addr) {
+        Some((TRAMPOLINE - 1 - addr) / KERNEL_STACK_SLOT_SIZE)
+    } else {
+        None
+    }
+}
+
 // 内核栈同样也可以视为资源，使用RAII风格对资源进行借用
 pub struct KernelStack {
-    pid: usize,
+    slot: usize,
+    // 内核栈大小，不超过KERNEL_STACK_MAX_SIZE
+    size: usize,
 }
 
 // 内核栈的方法
 impl KernelStack {
-    // 给进程句柄新分配一个内核栈
-    pub fn new(pid_handle: &PidHandle) -> Self {
-        // 获取pid数值
-        let pid = pid_handle.0;
-        // 查询这个pid对应的内核栈应该分配在哪
-        let (kernel_stack_bottom, kernel_stack_top) = kernel_stack_position(pid);
+    // 新分配一个size大小的内核栈，size需要按页对齐，页帧不够时返回None
+    pub fn new(size: usize) -> Option<Self> {
+        assert!(
+            size > 0 && size <= KERNEL_STACK_MAX_SIZE && size % PAGE_SIZE == 0,
+            "invalid kernel stack size {:#x}",
+            size
+        );
+        // 分配一个槽位，查询内核栈应该分配在哪
+        let slot = KSTACK_ALLOCATOR.lock().alloc();
+        let (kernel_stack_bottom, kernel_stack_top) = kernel_stack_position(slot, size);
         // 内核空间中插入一片用页帧分配器管理的地址用作这个进程的内核栈
-        KERNEL_SPACE.exclusive_access().insert_framed_area(
+        if !KERNEL_SPACE.lock().insert_framed_area(
             kernel_stack_bottom.into(),
             kernel_stack_top.into(),
             MapPermission::R | MapPermission::W,
-        );
+        ) {
+            KSTACK_ALLOCATOR.lock().dealloc(slot);
+            return None;
+        }
         // 封装资源抽象并返回
-        KernelStack { pid: pid_handle.0 }
+        Some(KernelStack { slot, size })
     }
     #[allow(unused)]
     // 内核栈把类型T的变量压栈，返回一个指向栈顶的该类型的指针
@@ -111,18 +173,27 @@ impl KernelStack {
     }
     // 获取栈顶指针
     pub fn get_top(&self) -> usize {
-        let (_, kernel_stack_top) = kernel_stack_position(self.pid);
+        let (_, kernel_stack_top) = kernel_stack_position(self.slot, self.size);
         kernel_stack_top
     }
+    // 获取槽位编号
+    pub fn slot(&self) -> usize {
+        self.slot
+    }
+    // 获取内核栈大小
+    pub fn size(&self) -> usize {
+        self.size
+    }
 }
 
 // 自动回收内核栈资源
 impl Drop for KernelStack {
     fn drop(&mut self) {
-        let (kernel_stack_bottom, _) = kernel_stack_position(self.pid);
+        let (kernel_stack_bottom, _) = kernel_stack_position(self.slot, self.size);
         let kernel_stack_bottom_va: VirtAddr = kernel_stack_bottom.into();
         KERNEL_SPACE
-            .exclusive_access()
+            .lock()
             .remove_area_with_start_vpn(kernel_stack_bottom_va.into());
+        KSTACK_ALLOCATOR.lock().dealloc(self.slot);
     }
 }
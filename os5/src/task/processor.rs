@@ -3,11 +3,17 @@
 use super::__switch;
 use super::{fetch_task, TaskStatus};
 use super::{TaskContext, TaskControlBlock};
-use crate::sync::UPSafeCell;
+use crate::config::MAX_HARTS;
+use crate::mm::MemorySet;
+use crate::sync::{irq_depth, IrqSafeCell, SpinNoIrq};
 use crate::trap::TrapContext;
-use crate::timer::get_time_us;
+use crate::timer::{check_timer, get_time_us, set_idle_trigger, set_next_trigger};
 use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::hint::spin_loop;
+use core::sync::atomic::{AtomicUsize, Ordering};
 use lazy_static::*;
+use riscv::register::sstatus;
 
 // 处理器资源抽象，处理器主要功能是可以运行进程
 pub struct Processor {
@@ -42,8 +48,42 @@ impl Processor {
 }
 
 lazy_static! {
-    // 初始化处理器
-    pub static ref PROCESSOR: UPSafeCell<Processor> = unsafe { UPSafeCell::new(Processor::new()) };
+    // 初始化处理器，每个核一个，只有这个核自己会访问，所以不需要锁
+    pub static ref PROCESSORS: Vec<IrqSafeCell<Processor>> = (0..MAX_HARTS)
+        .map(|_| unsafe { IrqSafeCell::new(Processor::new()) })
+        .collect();
+}
+
+// 当前核的编号，启动时放在tp中，内核里不会修改tp
+pub fn hart_id() -> usize {
+    let hart_id;
+    unsafe {
+        core::arch::asm!("mv {}, tp", out(reg) hart_id);
+    }
+    hart_id
+}
+
+// 已经启动、开始调度任务的核，第i位表示核i
+static ONLINE_HART_MASK: AtomicUsize = AtomicUsize::new(0);
+
+// 当前核启动完毕，进入调度循环前调用
+pub fn hart_online() {
+    ONLINE_HART_MASK.fetch_or(1 << hart_id(), Ordering::Release);
+}
+
+// 接口，已经启动的核数
+pub fn online_harts() -> usize {
+    online_hart_mask().count_ones() as usize
+}
+
+// 接口，已经启动的核的位图，只有它们可能缓存着用户地址空间的页表项
+pub fn online_hart_mask() -> usize {
+    ONLINE_HART_MASK.load(Ordering::Acquire)
+}
+
+// 当前核的处理器
+fn processor() -> &'static IrqSafeCell<Processor> {
+    &PROCESSORS[hart_id()]
 }
 
 // 开始运行任务，被main函数最后调用，开始进入用户态
@@ -52,9 +92,14 @@ pub fn run_tasks() {
     // 这个循环也被称为空闲上下文，任务让出cpu的时候就会回到这个循环，重新去调度器取新的进程
     loop {
         // 获取处理器修改能力
-        let mut processor = PROCESSOR.exclusive_access();
+        let mut processor = processor().exclusive_access();
         // 从任务调度器取一个任务，得到任务控制块
         if let Some(task) = fetch_task() {
+            // 任务刚在别的核上让出，等那个核保存完它的任务上下文
+            while task.on_cpu.load(Ordering::Acquire) {
+                spin_loop();
+            }
+            task.on_cpu.store(true, Ordering::Relaxed);
             // 获取空闲任务上下文
             let idle_task_cx_ptr = processor.get_idle_task_cx_ptr();
             // 访问任务控制块可变部分
@@ -70,25 +115,44 @@ pub fn run_tasks() {
             // 手动释放，因为后面直接就会去进程里不会回来了
             drop(task_inner);
             // 修改处理器状态
-            processor.current = Some(task);
+            processor.current = Some(task.clone());
             // 释放处理器
             drop(processor);
+            // 给新任务一个完整的时间片
+            set_next_trigger();
             // 从空闲进程切换到下一个进程
             unsafe {
                 __switch(idle_task_cx_ptr, next_task_cx_ptr);
             }
+            // 回到这个核的空闲控制流，说明任务上下文已经保存好，其他核可以运行它了
+            // 退出的任务可能在这里释放最后一个引用，此时已经不在它的内核栈上
+            task.on_cpu.store(false, Ordering::Release);
+        } else {
+            drop(processor);
+            idle();
         }
     }
 }
 
+// 没有就绪任务时的空闲路径，只为最早的睡眠任务设置时钟中断，然后wfi等待
+// sie中的时钟中断使能位一直是打开的，有时钟中断待处理时wfi就会返回，
+// 内核态sstatus.SIE为0，所以不会真的陷入，醒来后自己检查到期的睡眠任务
+fn idle() {
+    set_idle_trigger();
+    unsafe {
+        core::arch::asm!("wfi");
+    }
+    check_timer();
+}
+
 // 接口，获取当前处理器上正在运行的任务的控制块所有权
 pub fn take_current_task() -> Option<Arc<TaskControlBlock>> {
-    PROCESSOR.exclusive_access().take_current()
+    processor().exclusive_access().take_current()
 }
 
 // 接口，获取当前处理器上正在运行的任务的控制块引用计数引用
 pub fn current_task() -> Option<Arc<TaskControlBlock>> {
-    PROCESSOR.exclusive_access().current()
+    processor().exclusive_access().current()
 }
 
 // 获取当前任务的用户地址空间token
@@ -98,6 +162,23 @@ pub fn current_user_token() -> usize {
     token
 }
 
+// 获取当前任务的地址空间
+pub fn current_memory_set() -> Arc<SpinNoIrq<MemorySet>> {
+    current_task()
+        .unwrap()
+        .inner_exclusive_access()
+        .memory_set
+        .clone()
+}
+
+// 获取当前任务的trap上下文在用户地址空间中的位置
+pub fn current_trap_cx_user_va() -> usize {
+    current_task()
+        .unwrap()
+        .inner_exclusive_access()
+        .trap_cx_va
+}
+
 // 获取当前任务的可写trap上下文
 pub fn current_trap_cx() -> &'static mut TrapContext {
     current_task()
@@ -109,13 +190,25 @@ pub fn current_trap_cx() -> &'static mut TrapContext {
 // 切换到空闲任务上下文进行新的调度
 pub fn schedule(switched_task_cx_ptr: *mut TaskContext) {
     // 获取修改处理器的能力
-    let mut processor = PROCESSOR.exclusive_access();
+    let mut processor = processor().exclusive_access();
     // 获取空闲进程上下文
     let idle_task_cx_ptr = processor.get_idle_task_cx_ptr();
     // 手动释放，因为后面直接就会去进程里不会回来了
     drop(processor);
+    // 切换走时不能还持有锁，否则关中断的嵌套层数会被带到别的任务里
+    assert_eq!(irq_depth(), 0, "schedule while holding a lock");
+    // 空闲控制流总是关着中断运行，切换回来时再恢复这个任务自己的中断使能
+    let sie = sstatus::read().sie();
+    unsafe {
+        sstatus::clear_sie();
+    }
     // 切换任务
     unsafe {
         __switch(switched_task_cx_ptr, idle_task_cx_ptr);
     }
+    if sie {
+        unsafe {
+            sstatus::set_sie();
+        }
+    }
 }
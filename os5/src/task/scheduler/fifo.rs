@@ -0,0 +1,40 @@
+// FIFO调度，先来先服务，时钟中断不抢占，只有任务主动让出才切换
+
+use super::Scheduler;
+use crate::task::TaskControlBlock;
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+
+pub struct FifoScheduler {
+    // 就绪队列，双端队列
+    ready_queue: VecDeque<Arc<TaskControlBlock>>,
+}
+
+impl FifoScheduler {
+    pub fn new() -> Self {
+        Self {
+            ready_queue: VecDeque::new(),
+        }
+    }
+}
+
+impl Scheduler for FifoScheduler {
+    fn name(&self) -> &'static str {
+        "fifo"
+    }
+    // 压到队尾
+    fn add(&mut self, task: Arc<TaskControlBlock>) {
+        self.ready_queue.push_back(task);
+    }
+    // 从队首取
+    fn fetch(&mut self) -> Option<Arc<TaskControlBlock>> {
+        self.ready_queue.pop_front()
+    }
+    // 不抢占
+    fn on_tick(&mut self, _task: &Arc<TaskControlBlock>) -> bool {
+        false
+    }
+    fn on_yield(&mut self, _task: &Arc<TaskControlBlock>) {}
+    fn on_exit(&mut self, _task: &Arc<TaskControlBlock>) {}
+    fn set_tickets(&mut self, _task: &Arc<TaskControlBlock>, _tickets: usize) {}
+}
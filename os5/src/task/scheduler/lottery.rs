@@ -0,0 +1,76 @@
+// 彩票调度，每个任务持有一定数量的彩票，每次调度随机抽一张，持有者获得处理器
+// 长期来看各任务获得的处理器时间正比于彩票数
+
+use super::Scheduler;
+use crate::config::RANDOM_SEED;
+use crate::random::XorShift64;
+use crate::task::TaskControlBlock;
+use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+pub struct LotteryScheduler {
+    // 就绪任务
+    ready_tasks: Vec<Arc<TaskControlBlock>>,
+    // 各任务的彩票数，以pid为键，抽奖时不用再锁住每个任务；彩票数变化时由set_tickets更新，任务退出时移除
+    tickets: BTreeMap<usize, usize>,
+    // 固定种子的伪随机数发生器，便于复现
+    rng: XorShift64,
+}
+
+impl LotteryScheduler {
+    pub fn new() -> Self {
+        Self {
+            ready_tasks: Vec::new(),
+            tickets: BTreeMap::new(),
+            rng: XorShift64::new(RANDOM_SEED),
+        }
+    }
+}
+
+impl Scheduler for LotteryScheduler {
+    fn name(&self) -> &'static str {
+        "lottery"
+    }
+    // 第一次见到的任务从任务控制块中读出彩票数
+    fn add(&mut self, task: Arc<TaskControlBlock>) {
+        self.tickets
+            .entry(task.getpid())
+            .or_insert_with(|| task.inner_exclusive_access().task_tickets);
+        self.ready_tasks.push(task);
+    }
+    // 按彩票数抽出中奖任务
+    fn fetch(&mut self) -> Option<Arc<TaskControlBlock>> {
+        let tickets: Vec<usize> = self
+            .ready_tasks
+            .iter()
+            .map(|task| self.tickets[&task.getpid()])
+            .collect();
+        let total: usize = tickets.iter().sum();
+        if total == 0 {
+            return None;
+        }
+        let mut winner = self.rng.next_below(total);
+        let index = tickets.iter().position(|&t| {
+            if winner < t {
+                true
+            } else {
+                winner -= t;
+                false
+            }
+        })?;
+        Some(self.ready_tasks.swap_remove(index))
+    }
+    // 每个时钟中断都重新抽奖
+    fn on_tick(&mut self, _task: &Arc<TaskControlBlock>) -> bool {
+        true
+    }
+    fn on_yield(&mut self, _task: &Arc<TaskControlBlock>) {}
+    // 清理退出任务的彩票数
+    fn on_exit(&mut self, task: &Arc<TaskControlBlock>) {
+        self.tickets.remove(&task.getpid());
+    }
+    fn set_tickets(&mut self, task: &Arc<TaskControlBlock>, tickets: usize) {
+        self.tickets.insert(task.getpid(), tickets);
+    }
+}
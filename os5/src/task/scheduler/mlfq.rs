@@ -0,0 +1,94 @@
+// 多级反馈队列调度
+// 新任务从最高级开始；在某一级用完该级的时间片就降一级；
+// 主动让出不会降级，但已用掉的时间片也不会清零，避免任务靠频繁让出霸占高优先级；
+// 每隔一段时间把所有任务提升回最高级，防止低优先级的任务饿死
+
+use super::Scheduler;
+use crate::config::{MLFQ_BOOST_PERIOD_MS, MLFQ_TIME_SLICES};
+use crate::task::TaskControlBlock;
+use crate::timer::get_time_ms;
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+// 任务在多级反馈队列中的状态
+#[derive(Copy, Clone, Default)]
+struct MlfqState {
+    // 所在级别，0为最高级
+    level: usize,
+    // 在该级已经用掉的时钟中断数
+    used: usize,
+}
+
+pub struct MlfqScheduler {
+    // 每一级一个就绪队列
+    queues: Vec<VecDeque<Arc<TaskControlBlock>>>,
+    // 各任务的状态，以pid为键，任务退出时移除
+    states: BTreeMap<usize, MlfqState>,
+    // 上次提升的时刻，单位为毫秒，每个核都有时钟中断，所以按时间而不是中断次数算周期
+    last_boost_ms: usize,
+}
+
+impl MlfqScheduler {
+    pub fn new() -> Self {
+        Self {
+            queues: (0..MLFQ_TIME_SLICES.len()).map(|_| VecDeque::new()).collect(),
+            states: BTreeMap::new(),
+            last_boost_ms: 0,
+        }
+    }
+    // 所有任务提升回最高级，now是当前时刻
+    fn boost(&mut self, now: usize) {
+        for state in self.states.values_mut() {
+            *state = MlfqState::default();
+        }
+        let (top, lower) = self.queues.split_at_mut(1);
+        for queue in lower {
+            top[0].append(queue);
+        }
+        self.last_boost_ms = now;
+    }
+    // 是否有比指定级别更高的就绪任务
+    fn has_higher_than(&self, level: usize) -> bool {
+        self.queues[..level].iter().any(|queue| !queue.is_empty())
+    }
+}
+
+impl Scheduler for MlfqScheduler {
+    fn name(&self) -> &'static str {
+        "mlfq"
+    }
+    // 按任务所在级别放入对应队列
+    fn add(&mut self, task: Arc<TaskControlBlock>) {
+        let level = self.states.entry(task.getpid()).or_default().level;
+        self.queues[level].push_back(task);
+    }
+    // 从最高的非空级别取任务
+    fn fetch(&mut self) -> Option<Arc<TaskControlBlock>> {
+        self.queues.iter_mut().find_map(|queue| queue.pop_front())
+    }
+    // 用完时间片则降级并抢占；有更高级别的任务就绪时也抢占
+    fn on_tick(&mut self, task: &Arc<TaskControlBlock>) -> bool {
+        let now = get_time_ms();
+        if now.saturating_sub(self.last_boost_ms) >= MLFQ_BOOST_PERIOD_MS {
+            self.boost(now);
+            return true;
+        }
+        let lowest = self.queues.len() - 1;
+        let state = self.states.entry(task.getpid()).or_default();
+        state.used += 1;
+        if state.used >= MLFQ_TIME_SLICES[state.level] {
+            state.level = (state.level + 1).min(lowest);
+            state.used = 0;
+            return true;
+        }
+        let level = state.level;
+        self.has_higher_than(level)
+    }
+    fn on_yield(&mut self, _task: &Arc<TaskControlBlock>) {}
+    // 清理退出任务的状态
+    fn on_exit(&mut self, task: &Arc<TaskControlBlock>) {
+        self.states.remove(&task.getpid());
+    }
+    fn set_tickets(&mut self, _task: &Arc<TaskControlBlock>, _tickets: usize) {}
+}
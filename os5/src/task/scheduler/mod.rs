@@ -0,0 +1,69 @@
+// 可插拔的调度策略，TaskManager只负责持有一个调度器并转发调用
+
+mod fifo;
+mod lottery;
+mod mlfq;
+mod round_robin;
+mod stride;
+
+use super::TaskControlBlock;
+use alloc::boxed::Box;
+use alloc::sync::Arc;
+
+pub use fifo::FifoScheduler;
+pub use lottery::LotteryScheduler;
+pub use mlfq::MlfqScheduler;
+pub use round_robin::RoundRobinScheduler;
+pub use stride::StrideScheduler;
+
+// 调度策略特性，所有调度器都要实现
+pub trait Scheduler: Send {
+    // 策略名称，用于启动时打印
+    fn name(&self) -> &'static str;
+    // 将就绪的任务放入就绪队列
+    fn add(&mut self, task: Arc<TaskControlBlock>);
+    // 选出下一个要运行的任务
+    fn fetch(&mut self) -> Option<Arc<TaskControlBlock>>;
+    // 正在运行的任务经历了一次时钟中断，返回true表示应当抢占它
+    fn on_tick(&mut self, task: &Arc<TaskControlBlock>) -> bool;
+    // 正在运行的任务主动让出处理器，之后会被add压回就绪队列
+    fn on_yield(&mut self, task: &Arc<TaskControlBlock>);
+    // 正在运行的任务退出，之后不会再被add
+    fn on_exit(&mut self, task: &Arc<TaskControlBlock>);
+    // 任务的彩票数变成了tickets，调用时不持有任务的锁
+    fn set_tickets(&mut self, task: &Arc<TaskControlBlock>, tickets: usize);
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+// 可选的调度策略
+pub enum SchedPolicy {
+    Fifo,
+    RoundRobin,
+    Stride,
+    Mlfq,
+    Lottery,
+}
+
+impl SchedPolicy {
+    // 从名称解析调度策略，名称来自编译时的SCHED环境变量
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "fifo" => Some(Self::Fifo),
+            "rr" | "round_robin" => Some(Self::RoundRobin),
+            "stride" => Some(Self::Stride),
+            "mlfq" => Some(Self::Mlfq),
+            "lottery" => Some(Self::Lottery),
+            _ => None,
+        }
+    }
+    // 构建对应的调度器
+    pub fn build(self) -> Box<dyn Scheduler> {
+        match self {
+            Self::Fifo => Box::new(FifoScheduler::new()),
+            Self::RoundRobin => Box::new(RoundRobinScheduler::new()),
+            Self::Stride => Box::new(StrideScheduler::new()),
+            Self::Mlfq => Box::new(MlfqScheduler::new()),
+            Self::Lottery => Box::new(LotteryScheduler::new()),
+        }
+    }
+}
@@ -0,0 +1,49 @@
+// 时间片轮转调度，队列同FIFO，但任务用完时间片后被抢占
+
+use super::Scheduler;
+use crate::config::{MAX_HARTS, RR_TIME_SLICE};
+use crate::task::{hart_id, TaskControlBlock};
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+
+pub struct RoundRobinScheduler {
+    // 就绪队列，双端队列
+    ready_queue: VecDeque<Arc<TaskControlBlock>>,
+    // 每个核上当前运行的任务在本时间片内已经用掉的时钟中断数
+    ticks: [usize; MAX_HARTS],
+}
+
+impl RoundRobinScheduler {
+    pub fn new() -> Self {
+        Self {
+            ready_queue: VecDeque::new(),
+            ticks: [0; MAX_HARTS],
+        }
+    }
+}
+
+impl Scheduler for RoundRobinScheduler {
+    fn name(&self) -> &'static str {
+        "round_robin"
+    }
+    fn add(&mut self, task: Arc<TaskControlBlock>) {
+        self.ready_queue.push_back(task);
+    }
+    // 取出队首任务，同时在取任务的核上开启新的时间片
+    fn fetch(&mut self) -> Option<Arc<TaskControlBlock>> {
+        let task = self.ready_queue.pop_front();
+        if task.is_some() {
+            self.ticks[hart_id()] = 0;
+        }
+        task
+    }
+    // 时间片用完就抢占
+    fn on_tick(&mut self, _task: &Arc<TaskControlBlock>) -> bool {
+        let ticks = &mut self.ticks[hart_id()];
+        *ticks += 1;
+        *ticks >= RR_TIME_SLICE
+    }
+    fn on_yield(&mut self, _task: &Arc<TaskControlBlock>) {}
+    fn on_exit(&mut self, _task: &Arc<TaskControlBlock>) {}
+    fn set_tickets(&mut self, _task: &Arc<TaskControlBlock>, _tickets: usize) {}
+}
@@ -0,0 +1,109 @@
+// Stride调度，进程按优先级对应的步长增加长度，每次取用长度最短的进程
+// 长度允许溢出回绕：只要任意两个就绪进程的长度之差不超过usize范围的一半，
+// 把两者的差值当作有符号数就能得到正确的先后关系，而最大步长BIG_STRIDE / 2保证了这一点
+
+use super::Scheduler;
+use crate::config::BIG_STRIDE;
+use crate::task::TaskControlBlock;
+use alloc::collections::BinaryHeap;
+use alloc::sync::Arc;
+use core::cmp::Ordering;
+
+// 回绕安全的长度比较
+fn pass_cmp(a: usize, b: usize) -> Ordering {
+    (a.wrapping_sub(b) as isize).cmp(&0)
+}
+
+// 就绪堆中的一项，记录入堆时的长度和入堆序号
+struct StrideEntry {
+    pass: usize,
+    // 长度相同时先入堆的先出
+    seq: usize,
+    task: Arc<TaskControlBlock>,
+}
+
+impl PartialEq for StrideEntry {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for StrideEntry {}
+
+impl PartialOrd for StrideEntry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+// BinaryHeap是大顶堆，这里反过来比较，让长度最短的排在堆顶
+impl Ord for StrideEntry {
+    fn cmp(&self, other: &Self) -> Ordering {
+        pass_cmp(other.pass, self.pass).then_with(|| other.seq.cmp(&self.seq))
+    }
+}
+
+pub struct StrideScheduler {
+    // 就绪堆，按长度排序
+    ready_heap: BinaryHeap<StrideEntry>,
+    // 最近一次被选中的任务的长度，即全局最短长度
+    min_pass: usize,
+    // 入堆序号
+    seq: usize,
+}
+
+impl StrideScheduler {
+    pub fn new() -> Self {
+        Self {
+            ready_heap: BinaryHeap::new(),
+            min_pass: 0,
+            seq: 0,
+        }
+    }
+    // 任务让出处理器时，按优先级增加长度
+    fn advance_pass(task: &Arc<TaskControlBlock>) {
+        let mut inner = task.inner_exclusive_access();
+        let stride = (BIG_STRIDE / inner.task_priority).max(1);
+        inner.task_pass = inner.task_pass.wrapping_add(stride);
+    }
+}
+
+impl Scheduler for StrideScheduler {
+    fn name(&self) -> &'static str {
+        "stride"
+    }
+    // 新任务和落后于全局最短长度的任务从全局最短长度开始，
+    // 这样就绪任务之间的长度差始终不超过一个步长，比较不会因回绕出错
+    fn add(&mut self, task: Arc<TaskControlBlock>) {
+        let mut inner = task.inner_exclusive_access();
+        if inner.task_first_running_time.is_none()
+            || pass_cmp(inner.task_pass, self.min_pass) == Ordering::Less
+        {
+            inner.task_pass = self.min_pass;
+        }
+        let pass = inner.task_pass;
+        drop(inner);
+        self.seq = self.seq.wrapping_add(1);
+        self.ready_heap.push(StrideEntry {
+            pass,
+            seq: self.seq,
+            task,
+        });
+    }
+    // 取出长度最短的任务
+    fn fetch(&mut self) -> Option<Arc<TaskControlBlock>> {
+        let entry = self.ready_heap.pop()?;
+        self.min_pass = entry.pass;
+        Some(entry.task)
+    }
+    // 每个时钟中断都抢占
+    fn on_tick(&mut self, task: &Arc<TaskControlBlock>) -> bool {
+        Self::advance_pass(task);
+        true
+    }
+    fn on_yield(&mut self, task: &Arc<TaskControlBlock>) {
+        Self::advance_pass(task);
+    }
+    fn on_exit(&mut self, _task: &Arc<TaskControlBlock>) {}
+    fn set_tickets(&mut self, _task: &Arc<TaskControlBlock>, _tickets: usize) {}
+}
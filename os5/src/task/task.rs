@@ -2,36 +2,53 @@
 
 use super::TaskContext;
 use super::{pid_alloc, KernelStack, PidHandle};
-use crate::config::{TRAP_CONTEXT, MAX_SYSCALL_NUM};
-use crate::mm::{MemorySet, PhysPageNum, VirtAddr, KERNEL_SPACE};
-use crate::sync::UPSafeCell;
+use crate::config::{CLOCK_FREQ, DEFAULT_TICKETS, PAGE_SIZE, TRAP_CONTEXT, MAX_SYSCALL_NUM};
+use crate::mm::{
+    register_user_space, translated_refmut, AccessType, LoadError, MemorySet, PhysPageNum, VirtAddr,
+    KERNEL_SPACE,
+};
+use crate::random::fill_random;
+use crate::sync::{SpinNoIrq, SpinNoIrqGuard, WaitQueue};
 use crate::trap::{trap_handler, TrapContext};
+use alloc::string::String;
 use alloc::sync::{Arc, Weak};
 use alloc::vec::Vec;
-use core::cell::RefMut;
+use core::iter::once;
+use core::sync::atomic::AtomicBool;
 
 // 任务控制块分为初始化后就不可变的部分和运行中可变的部分，因为接下来要上Arc了，只能用内部可变
 pub struct TaskControlBlock {
     // 初始化后就不变的部分
     pub pid: PidHandle, // 应用的pid句柄，也是一种RAII风格资源抽象
     pub kernel_stack: KernelStack, // 应用的内核栈
+    pub child_exit_queue: WaitQueue, // 在waitpid中等待子进程退出时阻塞在这里
+    // 是否还占着某个核：从被调度运行开始，到切换回那个核的空闲控制流为止
+    // 为真时其他核不能切换到它的任务上下文，上下文可能还没有保存完
+    pub on_cpu: AtomicBool,
+    // vfork出来的子进程exec或退出之前，父进程阻塞在这里
+    pub vfork_done: WaitQueue,
 
-    // 运行中发生变化的部分
-    inner: UPSafeCell<TaskControlBlockInner>,
+    // 运行中发生变化的部分，多个核都可能访问，用自旋锁保护
+    // 同时持有父子进程的锁时，必须先锁父进程再锁子进程
+    inner: SpinNoIrq<TaskControlBlockInner>,
 }
 
 // 任务控制块的变化部分
 pub struct TaskControlBlockInner {
     // Trap上下文的物理页帧号
     pub trap_cx_ppn: PhysPageNum,
-    // 应用地址空间中从0x00开始到用户栈结束一共包含多少字节
+    // Trap上下文在用户地址空间中的位置，独占地址空间时是TRAP_CONTEXT，
+    // 和别的任务共享地址空间时各自占用其中不同的一页
+    pub trap_cx_va: usize,
+    // 用户栈的初始栈顶，用户栈放在固定的高地址，它下面不全是应用占用的内存
     pub base_size: usize,
     // 任务切换时挂起的上下文快照
     pub task_cx: TaskContext,
     // 任务状态
     pub task_status: TaskStatus,
-    // 任务的地址空间
-    pub memory_set: MemorySet,
+    // 任务的地址空间，clone时可以和父进程共享，用自旋锁保护
+    // 同时持有任务的锁时，必须先锁任务再锁地址空间
+    pub memory_set: Arc<SpinNoIrq<MemorySet>>,
     // 父进程，使用弱引用
     pub parent: Option<Weak<TaskControlBlock>>,
     // 子进程，使用强引用
@@ -46,6 +63,14 @@ pub struct TaskControlBlockInner {
     pub task_pass: usize,
     // 新增，进程的优先级
     pub task_priority: usize,
+    // 彩票调度中持有的彩票数，包含从父进程借来的
+    pub task_tickets: usize,
+    // 从父进程借来的彩票数，退出时归还
+    pub task_borrowed_tickets: usize,
+    // 环境变量，形如KEY=VALUE，fork和spawn时继承，exec时可以替换
+    pub envs: Vec<String>,
+    // 是否是还没有exec或退出的vfork子进程，父进程在vfork_done上等它变为false
+    pub vfork_pending: bool,
 }
 
 // 访问可变部分字段的方法
@@ -61,7 +86,7 @@ impl TaskControlBlockInner {
     }
     // 获取进程的地址空间token
     pub fn get_user_token(&self) -> usize {
-        self.memory_set.token()
+        self.memory_set.lock().token()
     }
     // 获取进程状态
     fn get_status(&self) -> TaskStatus {
@@ -77,154 +102,361 @@ impl TaskControlBlockInner {
         self.task_priority = prio as usize;
         prio
     }
+    // 设置彩票数
+    pub fn set_task_tickets(&mut self, tickets: isize) -> isize {
+        if tickets < 1 { return -1; }
+        self.task_tickets = tickets as usize;
+        tickets
+    }
 }
 
 // 任务控制块的方法
 impl TaskControlBlock {
     // 获取内部可变的引用
-    pub fn inner_exclusive_access(&self) -> RefMut<'_, TaskControlBlockInner> {
-        self.inner.exclusive_access()
+    pub fn inner_exclusive_access(&self) -> SpinNoIrqGuard<'_, TaskControlBlockInner> {
+        self.inner.lock()
+    }
+    // 尝试获取内部可变的引用，已被占用时返回None
+    pub fn inner_try_access(&self) -> Option<SpinNoIrqGuard<'_, TaskControlBlockInner>> {
+        self.inner.try_lock()
     }
 
-    // 直接从ELF新建一个进程，获得返回的任务控制块，目前只适用于用户初始程序，其他的靠fork和exec
-    pub fn new(elf_data: &[u8]) -> Self {
+    // 直接从ELF新建一个进程，获得返回的任务控制块，需要指定内核栈大小、命令行参数和环境变量
+    // ELF装载不了或者页帧不够时返回失败的原因
+    pub fn new(
+        elf_data: &[u8],
+        kernel_stack_size: usize,
+        args: Vec<String>,
+        envs: Vec<String>,
+    ) -> Result<Self, LoadError> {
         // 先用ELF新建进程地址空间
-        let (memory_set, user_sp, entry_point) = MemorySet::from_elf(elf_data);
+        let (memory_set, user_sp, entry_point, phdr) = MemorySet::from_elf(elf_data)?;
         // 获得trap上下文在进程地址空间中的物理地址
         let trap_cx_ppn = memory_set
             .translate(VirtAddr::from(TRAP_CONTEXT).into())
             .unwrap()
             .ppn();
-        // 分配一个pid，顺便分配内核栈
+        // 登记地址空间，之后它的页可以被换出
+        let memory_set = Arc::new(SpinNoIrq::new(memory_set));
+        register_user_space(&memory_set);
+        // 分配一个pid和内核栈
         let pid_handle = pid_alloc();
-        let kernel_stack = KernelStack::new(&pid_handle);
+        let kernel_stack = KernelStack::new(kernel_stack_size).ok_or(LoadError::NoMemory)?;
         let kernel_stack_top = kernel_stack.get_top();
         // 构造任务控制块
         let task_control_block = Self {
             // 不变部分，这两个就是在这里进行初始化就不变了的
             pid: pid_handle, // pid句柄
             kernel_stack, // 内核栈
+            child_exit_queue: WaitQueue::new(), // 等待子进程退出的队列
+            on_cpu: AtomicBool::new(false), // 还没有运行过
+            vfork_done: WaitQueue::new(), // 不是vfork出来的，没有人会等
             // 可变部分
-            inner: unsafe {
-                UPSafeCell::new(TaskControlBlockInner {
-                    trap_cx_ppn, // trap上下文物理页帧号
-                    base_size: user_sp, // 从0x00到用户栈顶结束，是整个大小
+            inner: SpinNoIrq::new(TaskControlBlockInner {
+                trap_cx_ppn, // trap上下文物理页帧号
+                trap_cx_va: TRAP_CONTEXT, // 独占地址空间
+                base_size: user_sp, // 用户栈的初始栈顶
 
-                    // 调用goto_trap_return方法，构建一个初次进入进程时的任务上下文
-                    // 需要提供内核栈顶，这样才能把构造好的任务上下文压到正确的位置（内核栈顶）
-                    task_cx: TaskContext::goto_trap_return(kernel_stack_top),
-                    task_status: TaskStatus::Ready, //进程状态：挂起
-                    memory_set, // 地址空间
-                    parent: None, // 直接创建，没有父进程
-                    children: Vec::new(), // 子进程为空
-                    exit_code: 0, // 退出码初始为0
-                    task_syscall_times: [0; MAX_SYSCALL_NUM], // 各种系统调用的次数
-                    task_first_running_time: None, // 任务第一次被调度的时刻
-                    task_pass: 0, // 运行长度
-                    task_priority: 16, // 优先级
-                })
-            },
+                // 调用goto_trap_return方法，构建一个初次进入进程时的任务上下文
+                // 需要提供内核栈顶，这样才能把构造好的任务上下文压到正确的位置（内核栈顶）
+                task_cx: TaskContext::goto_trap_return(kernel_stack_top),
+                task_status: TaskStatus::Ready, //进程状态：挂起
+                memory_set, // 地址空间
+                parent: None, // 直接创建，没有父进程
+                children: Vec::new(), // 子进程为空
+                exit_code: 0, // 退出码初始为0
+                task_syscall_times: [0; MAX_SYSCALL_NUM], // 各种系统调用的次数
+                task_first_running_time: None, // 任务第一次被调度的时刻
+                task_pass: 0, // 运行长度
+                task_priority: 16, // 优先级
+                task_tickets: DEFAULT_TICKETS, // 彩票数
+                task_borrowed_tickets: 0, // 借来的彩票数
+                envs, // 环境变量
+                vfork_pending: false,
+            }),
         };
         // 同时还需要构造trap上下文
         // 获取位置
-        let trap_cx = task_control_block.inner_exclusive_access().get_trap_cx();
+        let inner = task_control_block.inner_exclusive_access();
+        let trap_cx = inner.get_trap_cx();
         *trap_cx = TrapContext::app_init_context( // 构造trap上下文快照
             entry_point, // 程序入口点，放在trap恢复时执行的位置
             user_sp, // 用户栈指针，创建地址空间时得到的
-            KERNEL_SPACE.exclusive_access().token(), // 内核页表，固定写入
+            KERNEL_SPACE.lock().token(), // 内核页表，固定写入
             kernel_stack_top, // 内核栈指针，分配Pid时顺便分配的
             trap_handler as usize, // trap处理入口，固定写入
         );
+        // 把参数、环境变量和辅助向量压到用户栈上
+        init_user_stack(trap_cx, &mut inner.memory_set.lock(), &args, &inner.envs, phdr)
+            .ok_or(LoadError::NoMemory)?;
+        drop(inner);
         // 返回任务控制块
-        task_control_block
+        Ok(task_control_block)
     }
-    // 用一个新的elf替代原来进程的内容执行
-    pub fn exec(&self, elf_data: &[u8]) {
+    // 用一个新的elf替代原来进程的内容执行，args为命令行参数，envs为新的环境变量
+    // elf装载不了或者页帧不够时返回false，原来的进程不受影响
+    pub fn exec(&self, elf_data: &[u8], args: Vec<String>, envs: Vec<String>) -> bool {
         // 先用elf创建地址空间
-        let (memory_set, user_sp, entry_point) = MemorySet::from_elf(elf_data);
+        let (mut memory_set, user_sp, entry_point, phdr) = match MemorySet::from_elf(elf_data) {
+            Ok(loaded) => loaded,
+            Err(_) => return false,
+        };
         // 获得trap上下文的位置
         let trap_cx_ppn = memory_set
             .translate(VirtAddr::from(TRAP_CONTEXT).into())
             .unwrap()
             .ppn();
+        // 替换之前就在新的地址空间里构建好Trap上下文
+        let trap_cx: &mut TrapContext = trap_cx_ppn.get_mut();
+        *trap_cx = TrapContext::app_init_context(
+            entry_point,
+            user_sp,
+            KERNEL_SPACE.lock().token(),
+            self.kernel_stack.get_top(),
+            trap_handler as usize,
+        );
+        // 把参数、环境变量和辅助向量压到新的用户栈上
+        if init_user_stack(trap_cx, &mut memory_set, &args, &envs, phdr).is_none() {
+            return false;
+        }
 
         // **** 访问进程控制块的内部可变部分
         let mut inner = self.inner_exclusive_access();
+        // 离开和别的任务共享的地址空间时，把自己的Trap上下文页还回去
+        let trap_cx_va = inner.trap_cx_va;
+        let mut old_memory_set = inner.memory_set.lock();
+        if old_memory_set.is_shared() {
+            old_memory_set.dealloc_trap_cx(trap_cx_va);
+        }
+        old_memory_set.detach_task();
+        drop(old_memory_set);
         // 替换地址空间
-        inner.memory_set = memory_set;
+        inner.memory_set = Arc::new(SpinNoIrq::new(memory_set));
+        register_user_space(&inner.memory_set);
         // 替换优先级
         inner.task_priority = 16;
         // 替换Trap物理页帧号
         inner.trap_cx_ppn = trap_cx_ppn;
-        // 构建Trap上下文
-        let trap_cx = inner.get_trap_cx();
-        *trap_cx = TrapContext::app_init_context(
-            entry_point,
-            user_sp,
-            KERNEL_SPACE.exclusive_access().token(),
-            self.kernel_stack.get_top(),
-            trap_handler as usize,
-        );
-        // **** 自动释放内部可变的引用
+        inner.trap_cx_va = TRAP_CONTEXT;
+        inner.envs = envs;
+        // vfork的父进程可以继续运行了
+        let vfork_pending = core::mem::replace(&mut inner.vfork_pending, false);
+        drop(inner);
+        // **** 释放内部可变的引用
+        if vfork_pending {
+            self.vfork_done.wake_all();
+        }
+        true
     }
     // 复刻进程
-    pub fn fork(self: &Arc<TaskControlBlock>) -> Arc<TaskControlBlock> {
-        // ---- 独占访问父进程的可变部分
-        let mut parent_inner = self.inner_exclusive_access();
-        // 复刻父进程的地址空间
-        let memory_set = MemorySet::from_existed_user(&parent_inner.memory_set);
+    pub fn fork(self: &Arc<TaskControlBlock>) -> Option<Arc<TaskControlBlock>> {
+        self.clone_task(CloneFlags::empty(), 0)
+    }
+    // 按照flags复制出一个子进程，CLONE_VM时和父进程共享地址空间，否则复制一份
+    // stack不为0时作为子进程的用户栈指针，共享地址空间时必须给出，除非父进程vfork等待
+    // 子进程的Trap上下文中返回值已经设为0，CLONE_VFORK时由调用者负责等待
+    // 页帧不够时返回None，父进程的地址空间仍然可用
+    pub fn clone_task(
+        self: &Arc<TaskControlBlock>,
+        flags: CloneFlags,
+        stack: usize,
+    ) -> Option<Arc<TaskControlBlock>> {
+        // 先分配和父进程一样大的内核栈，这样加入共享的地址空间之后就不会再失败
+        let kernel_stack = KernelStack::new(self.kernel_stack.size())?;
+        let kernel_stack_top = kernel_stack.get_top();
+        let parent_memory_set = self.inner_exclusive_access().memory_set.clone();
+        let (memory_set, trap_cx_va) = if flags.contains(CloneFlags::CLONE_VM) {
+            // 共享地址空间，别的核上的任务可能缓存着页表项，共享之前把写时复制的页都复制出来
+            // 再给子进程在里面另分配一页Trap上下文
+            let mut memory_set = parent_memory_set.lock();
+            if !memory_set.break_cow() {
+                return None;
+            }
+            let trap_cx_va = memory_set.alloc_trap_cx()?;
+            memory_set.attach_task();
+            drop(memory_set);
+            (parent_memory_set, trap_cx_va)
+        } else {
+            // 复刻父进程的地址空间，期间锁住它，共享它的其他任务不能修改
+            // 只有父进程自己在用这个地址空间时才能写时复制
+            let mut parent = parent_memory_set.lock();
+            let cow = !parent.is_shared();
+            let memory_set = MemorySet::from_existed_user(&mut parent, cow)?;
+            drop(parent);
+            let memory_set = Arc::new(SpinNoIrq::new(memory_set));
+            register_user_space(&memory_set);
+            (memory_set, self.inner_exclusive_access().trap_cx_va)
+        };
         // 但是trap的物理页帧号还是要自己获取的
         let trap_cx_ppn = memory_set
-            .translate(VirtAddr::from(TRAP_CONTEXT).into())
+            .lock()
+            .translate(VirtAddr::from(trap_cx_va).into())
             .unwrap()
             .ppn();
-        // 分配一个pid，顺便分配内核栈
+        // ---- 独占访问父进程的可变部分
+        let mut parent_inner = self.inner_exclusive_access();
+        // 分配一个pid
         let pid_handle = pid_alloc();
-        let kernel_stack = KernelStack::new(&pid_handle);
-        let kernel_stack_top = kernel_stack.get_top();
         // 构造任务控制块
         let task_control_block = Arc::new(TaskControlBlock {
             pid: pid_handle,
             kernel_stack,
-            inner: unsafe {
-                UPSafeCell::new(TaskControlBlockInner {
-                    trap_cx_ppn,
-                    base_size: parent_inner.base_size,
-                    task_cx: TaskContext::goto_trap_return(kernel_stack_top),
-                    task_status: TaskStatus::Ready,
-                    memory_set,
-                    parent: Some(Arc::downgrade(self)),
-                    children: Vec::new(),
-                    exit_code: 0,
-                    task_syscall_times: parent_inner.task_syscall_times, // 各种系统调用的次数
-                    task_first_running_time: parent_inner.task_first_running_time, // 任务第一次被调度的时刻
-                    task_pass: parent_inner.task_pass, // 运行长度
-                    task_priority: parent_inner.task_priority, // 优先级
-                })
-            },
+            child_exit_queue: WaitQueue::new(),
+            on_cpu: AtomicBool::new(false),
+            vfork_done: WaitQueue::new(),
+            inner: SpinNoIrq::new(TaskControlBlockInner {
+                trap_cx_ppn,
+                trap_cx_va,
+                base_size: parent_inner.base_size,
+                task_cx: TaskContext::goto_trap_return(kernel_stack_top),
+                task_status: TaskStatus::Ready,
+                memory_set,
+                parent: Some(Arc::downgrade(self)),
+                children: Vec::new(),
+                exit_code: 0,
+                task_syscall_times: parent_inner.task_syscall_times, // 各种系统调用的次数
+                task_first_running_time: parent_inner.task_first_running_time, // 任务第一次被调度的时刻
+                task_pass: parent_inner.task_pass, // 运行长度
+                task_priority: parent_inner.task_priority, // 优先级
+                task_tickets: parent_inner.task_tickets, // 彩票数
+                task_borrowed_tickets: 0, // 借来的彩票数
+                envs: parent_inner.envs.clone(), // 环境变量
+                vfork_pending: flags.contains(CloneFlags::CLONE_VFORK),
+            }),
         });
         // 构建父子关系
         parent_inner.children.push(task_control_block.clone());
-        // 修改trap上下文中的栈顶指针
+        // 子进程的Trap上下文从父进程的复制过来，共享地址空间时它们不在同一页
+        // 修改其中的内核栈指针，子进程的返回值为0
         // **** 独占访问子进程可变部分
         let trap_cx = task_control_block.inner_exclusive_access().get_trap_cx();
+        *trap_cx = *parent_inner.get_trap_cx();
         trap_cx.kernel_sp = kernel_stack_top;
+        trap_cx.x[10] = 0;
+        if stack != 0 {
+            trap_cx.set_sp(stack);
+        }
         // 返回
-        task_control_block
+        Some(task_control_block)
         // ---- 释放父进程独占可变部分
         // **** 释放子进程独占可变部分
     }
+    // 等待vfork出来的子进程exec或退出
+    pub fn wait_vfork_done(&self) {
+        loop {
+            let inner = self.inner_exclusive_access();
+            if !inner.vfork_pending {
+                break;
+            }
+            // 挂上等待队列之后才释放子进程的锁，子进程在别的核上exec或退出时不会错过唤醒
+            self.vfork_done.wait_with(inner);
+        }
+    }
     // 获取pid值
     pub fn getpid(&self) -> usize {
         self.pid.0
     }
 }
 
+// 辅助向量的类型，取值和Linux一致
+const AT_NULL: usize = 0;
+const AT_PHDR: usize = 3;
+const AT_PAGESZ: usize = 6;
+const AT_ENTRY: usize = 9;
+// Linux中是times的计时频率，这里给出时钟计数器的频率，和get_time读到的计数一致
+const AT_CLKTCK: usize = 17;
+const AT_RANDOM: usize = 25;
+
+// 在新进程的用户栈上放好参数、环境变量和辅助向量，栈指针取自并写回Trap上下文
+// 栈顶向下依次是16个随机字节、环境变量字符串、参数字符串，
+// 然后从低地址到高地址依次是argv、envp（都以0结尾）和auxv（以AT_NULL结尾），栈指针指向argv
+// 用户程序的_start从a0~a3依次取得argc、argv、envp、auxv，用户栈的页分配不了时返回None
+fn init_user_stack(
+    trap_cx: &mut TrapContext,
+    memory_set: &mut MemorySet,
+    args: &[String],
+    envs: &[String],
+    phdr: usize,
+) -> Option<()> {
+    let word = core::mem::size_of::<usize>();
+    let mut user_sp = trap_cx.x[2];
+    // 随机字节，可以作为用户程序的随机数种子
+    let mut random = [0u8; 16];
+    fill_random(&mut random);
+    user_sp -= random.len();
+    let random_addr = user_sp;
+    copy_to_user(memory_set, random_addr, &random)?;
+    // 各个以0结尾的字符串
+    let mut push_str = |s: &String| {
+        user_sp -= s.len() + 1;
+        copy_to_user(memory_set, user_sp, s.as_bytes())?;
+        copy_to_user(memory_set, user_sp + s.len(), &[0])?;
+        Some(user_sp)
+    };
+    let env_ptrs: Vec<usize> = envs.iter().map(&mut push_str).collect::<Option<_>>()?;
+    let arg_ptrs: Vec<usize> = args.iter().map(&mut push_str).collect::<Option<_>>()?;
+    // 程序头表没有被装载时不提供AT_PHDR
+    let mut auxv = Vec::new();
+    if phdr != 0 {
+        auxv.push((AT_PHDR, phdr));
+    }
+    auxv.push((AT_PAGESZ, PAGE_SIZE));
+    auxv.push((AT_ENTRY, trap_cx.sepc));
+    auxv.push((AT_CLKTCK, CLOCK_FREQ));
+    auxv.push((AT_RANDOM, random_addr));
+    auxv.push((AT_NULL, 0));
+    // 指针数组，栈指针按16字节对齐
+    let words = arg_ptrs.len() + 1 + env_ptrs.len() + 1 + auxv.len() * 2;
+    user_sp = (user_sp - words * word) & !0xf;
+    let argv_base = user_sp;
+    let envp_base = argv_base + (arg_ptrs.len() + 1) * word;
+    let auxv_base = envp_base + (env_ptrs.len() + 1) * word;
+    let values = arg_ptrs
+        .iter()
+        .copied()
+        .chain(once(0))
+        .chain(env_ptrs.iter().copied())
+        .chain(once(0))
+        .chain(auxv.iter().flat_map(|&(key, value)| once(key).chain(once(value))));
+    for (i, value) in values.enumerate() {
+        copy_to_user(memory_set, argv_base + i * word, &value.to_le_bytes())?;
+    }
+    trap_cx.x[2] = user_sp;
+    trap_cx.x[10] = args.len();
+    trap_cx.x[11] = argv_base;
+    trap_cx.x[12] = envp_base;
+    trap_cx.x[13] = auxv_base;
+    Some(())
+}
+
+// 把内核中的字节拷贝到用户地址空间的va处，可以跨页，用户栈的页是第一次用到时才分配的，分配不了时返回None
+fn copy_to_user(memory_set: &mut MemorySet, va: usize, bytes: &[u8]) -> Option<()> {
+    if !memory_set.prepare_access(va, bytes.len(), AccessType::Write) {
+        return None;
+    }
+    let token = memory_set.token();
+    for (i, b) in bytes.iter().enumerate() {
+        *translated_refmut(token, (va + i) as *mut u8) = *b;
+    }
+    Some(())
+}
+
+bitflags! {
+    // clone的标志，取值和Linux一致
+    pub struct CloneFlags: usize {
+        // 和父进程共享地址空间
+        const CLONE_VM = 0x100;
+        // 父进程阻塞到子进程exec或退出为止
+        const CLONE_VFORK = 0x4000;
+    }
+}
+
 #[derive(Copy, Clone, PartialEq)]
-// 四种进程状态：未启动、挂起、运行中、僵尸
+// 五种进程状态：未启动、挂起、运行中、阻塞、僵尸
 pub enum TaskStatus {
     UnInit,
     Ready,
     Running,
+    Blocked,
     Zombie,
 }
@@ -1,18 +1,133 @@
-use crate::config::CLOCK_FREQ;
+use crate::config::{CLOCK_FREQ, MAX_HARTS};
 use crate::sbi::set_timer;
+use crate::sync::{IrqSafeCell, SpinNoIrq};
+use crate::task::{hart_id, online_harts, wakeup_task, TaskControlBlock};
+use alloc::collections::BinaryHeap;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::cmp::Ordering;
+use lazy_static::*;
 use riscv::register::time;
 
 const TICKS_PER_SEC: usize = 100;
+const MSEC_PER_SEC: usize = 1000;
 const MICRO_PER_SEC: usize = 1_000_000;
 
 pub fn get_time() -> usize {
     time::read()
 }
 
+pub fn get_time_ms() -> usize {
+    time::read() / (CLOCK_FREQ / MSEC_PER_SEC)
+}
+
 pub fn get_time_us() -> usize {
     time::read() / (CLOCK_FREQ / MICRO_PER_SEC)
 }
 
+lazy_static! {
+    // 每个核当前时间片的到期时刻，空闲时为usize::MAX，只有这个核自己会访问
+    static ref TICK_DEADLINES: Vec<IrqSafeCell<usize>> = (0..MAX_HARTS)
+        .map(|_| unsafe { IrqSafeCell::new(0) })
+        .collect();
+}
+
+// 当前核的时间片到期时刻
+fn current_tick_deadline() -> &'static IrqSafeCell<usize> {
+    &TICK_DEADLINES[hart_id()]
+}
+
+// 设置当前核的时钟中断，时刻取时间片到期和最早的睡眠任务到期中较早的一个
+fn program_timer(tick_deadline: usize) {
+    let next = match TIMERS.lock().peek() {
+        Some(timer) => tick_deadline.min(timer.expire_ms * (CLOCK_FREQ / MSEC_PER_SEC)),
+        None => tick_deadline,
+    };
+    set_timer(next);
+}
+
+// 开始新的时间片
 pub fn set_next_trigger() {
-    set_timer(get_time() + CLOCK_FREQ / TICKS_PER_SEC);
+    let tick_deadline = get_time() + CLOCK_FREQ / TICKS_PER_SEC;
+    *current_tick_deadline().exclusive_access() = tick_deadline;
+    program_timer(tick_deadline);
+}
+
+// 空闲时不需要时间片，只为最早的睡眠任务设置时钟中断，没有睡眠任务就不再产生时钟中断
+// 多核时其他核随时可能加入就绪任务，空闲的核还是按时间片周期醒来检查
+pub fn set_idle_trigger() {
+    *current_tick_deadline().exclusive_access() = usize::MAX;
+    if online_harts() > 1 {
+        program_timer(get_time() + CLOCK_FREQ / TICKS_PER_SEC);
+    } else {
+        program_timer(usize::MAX);
+    }
+}
+
+// 处理时钟中断，唤醒到期的睡眠任务，返回当前时间片是否到期
+pub fn handle_timer_interrupt() -> bool {
+    check_timer();
+    let tick_deadline = *current_tick_deadline().exclusive_access();
+    if get_time() >= tick_deadline {
+        set_next_trigger();
+        true
+    } else {
+        program_timer(tick_deadline);
+        false
+    }
+}
+
+// 定时器，到期时唤醒睡眠的任务
+pub struct TimerCondVar {
+    // 到期时刻，单位为毫秒
+    pub expire_ms: usize,
+    pub task: Arc<TaskControlBlock>,
+}
+
+impl PartialEq for TimerCondVar {
+    fn eq(&self, other: &Self) -> bool {
+        self.expire_ms == other.expire_ms
+    }
+}
+
+impl Eq for TimerCondVar {}
+
+impl PartialOrd for TimerCondVar {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+// BinaryHeap是大顶堆，这里反过来比较，让最早到期的排在堆顶
+impl Ord for TimerCondVar {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.expire_ms.cmp(&self.expire_ms)
+    }
+}
+
+lazy_static! {
+    // 按到期时刻排序的定时器队列，所有核共享，用自旋锁保护
+    static ref TIMERS: SpinNoIrq<BinaryHeap<TimerCondVar>> = SpinNoIrq::new(BinaryHeap::new());
+}
+
+// 添加定时器，任务在expire_ms时刻被唤醒
+pub fn add_timer(expire_ms: usize, task: Arc<TaskControlBlock>) {
+    TIMERS.lock().push(TimerCondVar { expire_ms, task });
+}
+
+// 唤醒所有已经到期的任务，在每次时钟中断时调用
+pub fn check_timer() {
+    let current_ms = get_time_ms();
+    loop {
+        let mut timers = TIMERS.lock();
+        match timers.peek() {
+            Some(timer) if timer.expire_ms <= current_ms => {
+                let timer = timers.pop().unwrap();
+                // 释放定时器队列后再唤醒
+                drop(timers);
+                wakeup_task(timer.task);
+            }
+            _ => break,
+        }
+    }
 }
@@ -1,8 +1,10 @@
 // Trap上下文的实现，目前Trap上下文是放在各个用户地址空间的一个特定位置上
 
+use core::fmt;
 use riscv::register::sstatus::{self, Sstatus, SPP};
 
 #[repr(C)]
+#[derive(Clone, Copy)]
 // 保存的Trap上下文结构，与汇编中的压栈顺序一致
 pub struct TrapContext {
     // 32个通用寄存器
@@ -19,6 +21,8 @@ pub struct TrapContext {
     pub kernel_sp: usize,
     // trap处理入口
     pub trap_handler: usize,
+    // 返回用户态时所在核的编号，Trap时恢复到tp
+    pub hart_id: usize,
 }
 
 // Trap上下文方法，可用用于初次进入进程时构建挂起的Trap恢复快照
@@ -38,6 +42,8 @@ impl TrapContext {
         let mut sstatus = sstatus::read();
         // 设置恢复到用户态
         sstatus.set_spp(SPP::User);
+        // 创建时内核可能开着中断，__restore写回sstatus时必须是关中断的
+        sstatus.set_sie(false);
         // 由于还没运行，所以大部分寄存器设置空的就好
         let mut cx = Self {
             x: [0; 32],
@@ -46,9 +52,63 @@ impl TrapContext {
             kernel_satp,
             kernel_sp,
             trap_handler,
+            hart_id: 0,
         };
         // 栈指针要设置
         cx.set_sp(sp);
         cx
     }
 }
+
+#[repr(C)]
+// 内核态Trap时保存的上下文，中断时在当前内核栈上，异常时在本核的异常栈上，与汇编中的压栈顺序一致
+pub struct KernelTrapContext {
+    // 32个通用寄存器，其中tp没有保存
+    pub x: [usize; 32],
+    // Trap前的sstatus，SPP为S态
+    pub sstatus: usize,
+    // Trap前执行的指令地址
+    pub sepc: usize,
+}
+
+// 通用寄存器的ABI名称
+const REG_NAMES: [&str; 32] = [
+    "zero", "ra", "sp", "gp", "tp", "t0", "t1", "t2", "s0", "s1", "a0", "a1", "a2", "a3", "a4",
+    "a5", "a6", "a7", "s2", "s3", "s4", "s5", "s6", "s7", "s8", "s9", "s10", "s11", "t3", "t4",
+    "t5", "t6",
+];
+
+// 每行四个打印全部通用寄存器
+fn fmt_regs(f: &mut fmt::Formatter<'_>, x: &[usize; 32]) -> fmt::Result {
+    for (i, (name, value)) in REG_NAMES.iter().zip(x.iter()).enumerate() {
+        write!(f, "{:>4} = {:#018x}", name, value)?;
+        if i % 4 == 3 {
+            writeln!(f)?;
+        } else {
+            write!(f, "  ")?;
+        }
+    }
+    Ok(())
+}
+
+// 打印全部寄存器，内核出错时使用
+impl fmt::Display for KernelTrapContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "sepc = {:#018x}, sstatus = {:#018x}", self.sepc, self.sstatus)?;
+        fmt_regs(f, &self.x)
+    }
+}
+
+// 打印用户态的寄存器和内核栈信息，内核出错时使用
+impl fmt::Display for TrapContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "sepc = {:#018x}, sstatus = {:#018x}, kernel_sp = {:#018x}",
+            self.sepc,
+            self.sstatus.bits(),
+            self.kernel_sp
+        )?;
+        fmt_regs(f, &self.x)
+    }
+}
@@ -2,31 +2,38 @@
 
 mod context;
 
-use crate::config::{TRAMPOLINE, TRAP_CONTEXT};
+use crate::config::{MAX_HARTS, TRAMPOLINE};
+use crate::mm::AccessType;
 use crate::syscall::syscall;
+use crate::sync::irq_depth;
 use crate::task::{
-    current_trap_cx, current_user_token, exit_current_and_run_next, suspend_current_and_run_next,
+    current_memory_set, current_task, current_trap_cx, current_trap_cx_user_va,
+    current_user_token, exit_current_and_run_next, kernel_stack_overflow_slot,
+    preempt_current_and_run_next,
 };
-use crate::timer::set_next_trigger;
+use crate::timer::handle_timer_interrupt;
 use riscv::register::{
     mtvec::TrapMode,
     scause::{self, Exception, Interrupt, Trap},
-    sie, stval, stvec,
+    sie, sstatus, stval, stvec,
 };
 
 // 陷入要用到的汇编，放进来一起编译，实际上会被作为跳板段布置到各个新建的地址空间的最头部
-core::arch::global_asm!(include_str!("trap.S"));
+// 内核态的Trap入口也在里面，但放在普通的代码段
+core::arch::global_asm!(include_str!("trap.S"), MAX_HARTS = const MAX_HARTS);
 
-// 初始化Trap处理，因为最开始是在内核中，所以设置为内核的Trap处理入口（指直接panic）
+// 初始化Trap处理，因为最开始是在内核中，所以设置为内核的Trap处理入口
 pub fn init() {
     set_kernel_trap_entry();
 }
 
 // 设置内核态的Trap处理入口，stvec寄存器的作用就是用来存Trap处理函数入口的
 fn set_kernel_trap_entry() {
+    extern "C" {
+        fn __kernel_trap();
+    }
     unsafe {
-        // 用这个会panic的函数处理内核的trap（定义在最下面）
-        stvec::write(trap_from_kernel as usize, TrapMode::Direct);
+        stvec::write(__kernel_trap as usize, TrapMode::Direct);
     }
 }
 
@@ -61,11 +68,19 @@ pub fn trap_handler() -> ! {
             let mut cx = current_trap_cx();
             // 让中断位置指针向前步进1个指令，表示这个调用已经受理了
             cx.sepc += 4;
+            // 系统调用期间打开中断，耗时的系统调用也能被时钟中断抢占
+            // scause等寄存器已经读出来了，Trap上下文也已经保存在用户地址空间里，嵌套的Trap不会破坏它们
+            unsafe {
+                sstatus::set_sie();
+            }
             // 跳转到对应的系统调用处理函数
             let result = syscall(cx.x[17], [cx.x[10], cx.x[11], cx.x[12]]); // 任务切换的快照恢复后总是会出现在这里（略去里面那层系统调用的函数的话），
             // 通过switch修改了ra导致ret时回到了另一个进程的这个断点，
             // 同时旧的进程也是因为能够跳回这里的ra被快照后修改了所以才跳到另一个进程去了，现在还是在内核态所以保存的也是内核栈sp
             
+            unsafe {
+                sstatus::clear_sie();
+            }
             // 系统调用可能会修改上下文，重新获取
             cx = current_trap_cx();
             // 给出结果（0或-1）
@@ -78,15 +93,26 @@ pub fn trap_handler() -> ! {
         | Trap::Exception(Exception::InstructionPageFault)
         | Trap::Exception(Exception::LoadFault)
         | Trap::Exception(Exception::LoadPageFault) => {
-            // 打印错误信息
-            println!(
-                "[kernel] {:?} in application, bad addr = {:#x}, bad instruction = {:#x}, core dumped.",
-                scause.cause(),
-                stval,
-                current_trap_cx().sepc,
-            );
-            // 杀死进程，给出退出码
-            exit_current_and_run_next(-2);
+            // 能处理的缺页处理完就回去重新执行那条指令
+            if !handle_page_fault(scause.cause(), stval) {
+                // 打印错误信息，访问用户栈的保护区的是栈溢出
+                if current_memory_set().lock().in_stack_guard(stval) {
+                    println!(
+                        "[kernel] Stack overflow in application, bad addr = {:#x}, bad instruction = {:#x}, core dumped.",
+                        stval,
+                        current_trap_cx().sepc,
+                    );
+                } else {
+                    println!(
+                        "[kernel] {:?} in application, bad addr = {:#x}, bad instruction = {:#x}, core dumped.",
+                        scause.cause(),
+                        stval,
+                        current_trap_cx().sepc,
+                    );
+                }
+                // 杀死进程，给出退出码
+                exit_current_and_run_next(-2);
+            }
         }
         // 无效指令
         Trap::Exception(Exception::IllegalInstruction) => {
@@ -96,9 +122,10 @@ pub fn trap_handler() -> ! {
         }
         // 时钟中断
         Trap::Interrupt(Interrupt::SupervisorTimer) => {
-            set_next_trigger(); // 设置新的时钟中断
-            // 挂起进程
-            suspend_current_and_run_next();
+            // 唤醒到期的睡眠任务，时间片到期时由调度策略决定是否挂起进程
+            if handle_timer_interrupt() {
+                preempt_current_and_run_next();
+            }
         }
         // 未知错误
         _ => {
@@ -113,14 +140,30 @@ pub fn trap_handler() -> ! {
     trap_return();
 }
 
+// 处理用户态的缺页，返回是否处理了，没处理的就是访存错误
+fn handle_page_fault(cause: Trap, stval: usize) -> bool {
+    // 按需分配的页第一次被访问，或写时复制的页第一次被写
+    let access = match cause {
+        Trap::Exception(Exception::LoadPageFault) => AccessType::Read,
+        Trap::Exception(Exception::StorePageFault) => AccessType::Write,
+        Trap::Exception(Exception::InstructionPageFault) => AccessType::Execute,
+        _ => return false,
+    };
+    current_memory_set().lock().handle_page_fault(stval, access)
+}
+
 #[no_mangle]
 // 处理完Trap后的返回
 pub fn trap_return() -> ! {
+    // 换成用户态的Trap入口之后到sret之前不能再有中断
+    unsafe {
+        sstatus::clear_sie();
+    }
     // 要进入用户态了，把Trap处理函数换回去
     set_user_trap_entry();
-    // trap上下文在每个用户的地址空间中都是同一个固定位置，
-    // 所以在汇编里也没必要保存了，每次通过a0传给它就行了
-    let trap_cx_ptr = TRAP_CONTEXT;
+    // trap上下文在用户地址空间中的位置，独占地址空间时都是TRAP_CONTEXT，
+    // 共享地址空间的任务各占一页，汇编里不保存，每次通过a0传给它就行了
+    let trap_cx_ptr = current_trap_cx_user_va();
     // 用户页表的位置
     let user_satp = current_user_token();
     // 导入符号
@@ -146,9 +189,63 @@ pub fn trap_return() -> ! {
 }
 
 #[no_mangle]
-// 内核态遇到Trap直接panic
-pub fn trap_from_kernel() -> ! {
-    panic!("a trap {:?} from kernel!", scause::read().cause());
+// 内核态的Trap处理，上下文由__kernel_trap保存
+// 只有系统调用期间中断是打开的，而持有任何锁时中断都是关的，
+// 所以时钟中断到来时正处在可以安全切换任务的位置
+pub fn kernel_trap_handler(cx: &mut KernelTrapContext) {
+    let scause = scause::read();
+    let stval = stval::read();
+    match scause.cause() {
+        // 时钟中断，时间片到期就和在用户态一样交给调度策略决定是否抢占
+        Trap::Interrupt(Interrupt::SupervisorTimer) => {
+            if handle_timer_interrupt() && irq_depth() == 0 && current_task().is_some() {
+                preempt_current_and_run_next();
+            }
+        }
+        // 其他的都是内核的错误，打印全部寄存器
+        _ => {
+            let pid = current_task().map(|task| task.getpid());
+            println!(
+                "[kernel] {:?} in kernel, stval = {:#x}, current pid = {:?}",
+                scause.cause(),
+                stval,
+                pid
+            );
+            println!("{}", cx);
+            if let Trap::Exception(Exception::LoadPageFault | Exception::StorePageFault) =
+                scause.cause()
+            {
+                if let Some(slot) = kernel_stack_overflow_slot(stval) {
+                    report_kernel_stack_overflow(slot);
+                }
+            }
+            panic!("a trap {:?} from kernel!", scause.cause());
+        }
+    }
+}
+
+// 报告内核栈溢出，溢出的是当前任务的内核栈时打印它的pid、任务上下文和Trap上下文
+fn report_kernel_stack_overflow(slot: usize) {
+    let task = match current_task() {
+        Some(task) if task.kernel_stack.slot() == slot => task,
+        _ => {
+            println!("[kernel] kernel stack overflow in slot {}", slot);
+            return;
+        }
+    };
+    let pid = task.getpid();
+    println!("[kernel] kernel stack overflow of pid {} (slot {})", pid, slot);
+    // 出错时可能正持有这个任务的锁，拿不到就不打印了
+    let inner = task.inner_try_access();
+    match inner {
+        Some(inner) => {
+            println!("[kernel] last saved {:?}", inner.task_cx);
+            println!("[kernel] TrapContext of pid {}:\n{}", pid, inner.get_trap_cx());
+        }
+        None => {
+            println!("[kernel] task of pid {} is locked, context not dumped", pid);
+        }
+    }
 }
 
-pub use context::TrapContext;
+pub use context::{KernelTrapContext, TrapContext};
@@ -0,0 +1,69 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::{clone, exec, exit, fork, vfork, waitpid, CLONE_VFORK, CLONE_VM};
+
+/// 测试 clone 共享地址空间和 vfork 的父进程等待。
+/// 正确输出：（无报错信息）
+/// Test clone OK!
+
+const STACK_SIZE: usize = 4096 * 2;
+
+#[repr(align(16))]
+struct Stack([u8; STACK_SIZE]);
+
+static mut CHILD_STACK: Stack = Stack([0; STACK_SIZE]);
+static mut SHARED: usize = 0;
+
+fn stack_top() -> usize {
+    unsafe { CHILD_STACK.0.as_ptr() as usize + STACK_SIZE }
+}
+
+extern "C" fn set_shared(value: usize) -> i32 {
+    unsafe {
+        core::ptr::write_volatile(&mut SHARED, value);
+    }
+    0
+}
+
+fn shared() -> usize {
+    unsafe { core::ptr::read_volatile(&SHARED) }
+}
+
+fn wait_exit(pid: isize) -> i32 {
+    assert!(pid > 0);
+    let mut xstate: i32 = 0;
+    assert_eq!(waitpid(pid as usize, &mut xstate), pid);
+    xstate
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    // 共享地址空间，子进程的修改父进程看得到
+    let pid = clone(set_shared, stack_top(), CLONE_VM, 42);
+    assert_eq!(wait_exit(pid), 0);
+    assert_eq!(shared(), 42);
+    // fork出来的子进程有自己的一份
+    let pid = fork();
+    if pid == 0 {
+        set_shared(7);
+        exit(0);
+    }
+    assert_eq!(wait_exit(pid), 0);
+    assert_eq!(shared(), 42);
+    // CLONE_VFORK时clone返回之前子进程已经退出
+    let pid = clone(set_shared, stack_top(), CLONE_VM | CLONE_VFORK, 5);
+    assert_eq!(shared(), 5);
+    assert_eq!(wait_exit(pid), 0);
+    // vfork的子进程借用父进程的栈，只能exec或者exit，exec之后父进程才继续
+    let pid = vfork();
+    if pid == 0 {
+        exec("ch5_exit0\0", &[core::ptr::null::<u8>()]);
+        exit(-1);
+    }
+    assert_eq!(wait_exit(pid), 66778);
+    println!("Test clone OK!");
+    0
+}
@@ -0,0 +1,79 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::{exit, fork, sleep, waitpid};
+
+/// 测试写时复制的fork：父子进程的写互不可见，内核写用户内存时也会先复制。
+/// 正确输出：（无报错信息）
+/// Test copy on write OK!
+
+const LEN: usize = 4096;
+
+static mut DATA: [usize; LEN] = [0; LEN];
+
+fn data(i: usize) -> usize {
+    unsafe { core::ptr::read_volatile(&DATA[i]) }
+}
+
+fn set_data(i: usize, value: usize) {
+    unsafe { core::ptr::write_volatile(&mut DATA[i], value) }
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    for i in 0..LEN {
+        set_data(i, i);
+    }
+    // 子进程的写父进程看不到
+    let pid = fork();
+    if pid == 0 {
+        for i in 0..LEN {
+            assert_eq!(data(i), i);
+            set_data(i, i * 2);
+        }
+        for i in 0..LEN {
+            assert_eq!(data(i), i * 2);
+        }
+        exit(0);
+    }
+    let mut xstate: i32 = 0;
+    assert_eq!(waitpid(pid as usize, &mut xstate), pid);
+    assert_eq!(xstate, 0);
+    for i in 0..LEN {
+        assert_eq!(data(i), i);
+    }
+    // 父进程的写子进程看不到
+    let pid = fork();
+    if pid == 0 {
+        sleep(20);
+        for i in 0..LEN {
+            assert_eq!(data(i), i);
+        }
+        exit(0);
+    }
+    for i in 0..LEN {
+        set_data(i, i + 1);
+    }
+    assert_eq!(waitpid(pid as usize, &mut xstate), pid);
+    assert_eq!(xstate, 0);
+    // 内核写入waitpid的退出码时，仍在运行的子进程看到的还是原来的值
+    let mut code: i32 = 12345;
+    let watcher = fork();
+    if watcher == 0 {
+        sleep(20);
+        let code = unsafe { core::ptr::read_volatile(&code) };
+        exit(if code == 12345 { 0 } else { 1 });
+    }
+    let pid = fork();
+    if pid == 0 {
+        exit(7);
+    }
+    assert_eq!(waitpid(pid as usize, &mut code), pid);
+    assert_eq!(code, 7);
+    assert_eq!(waitpid(watcher as usize, &mut xstate), watcher);
+    assert_eq!(xstate, 0);
+    println!("Test copy on write OK!");
+    0
+}
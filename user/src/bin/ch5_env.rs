@@ -0,0 +1,54 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::env::{self, AT_CLKTCK, AT_ENTRY, AT_PAGESZ};
+use user_lib::{exec, execve, exit, fork, waitpid};
+
+/// 测试环境变量和辅助向量：execve 替换环境变量，exec 沿用当前的环境变量。
+/// 正确输出：（无报错信息）
+/// Test env OK!
+
+fn check_auxv() {
+    assert_eq!(env::aux(AT_PAGESZ), Some(4096));
+    assert!(env::aux(AT_ENTRY).unwrap() != 0);
+    assert!(env::aux(AT_CLKTCK).unwrap() != 0);
+    assert!(env::random_bytes().unwrap().iter().any(|&b| b != 0));
+}
+
+#[no_mangle]
+pub fn main(argc: usize, argv: &[&str]) -> i32 {
+    check_auxv();
+    if argc > 1 {
+        // 被execve之后，环境变量已被替换
+        assert_eq!(env::var("FOO"), Some("bar"));
+        assert_eq!(env::var("EMPTY"), Some(""));
+        assert_eq!(env::var("MISSING"), None);
+        assert_eq!(env::vars().count(), 2);
+        if argv[1] == "replaced" {
+            // 再用exec沿用当前的环境变量
+            exec(
+                "ch5_env\0",
+                &["ch5_env\0".as_ptr(), "inherited\0".as_ptr(), core::ptr::null::<u8>()],
+            );
+            return -1;
+        }
+        assert_eq!(argv[1], "inherited");
+        return 0;
+    }
+    let pid = fork();
+    if pid == 0 {
+        execve(
+            "ch5_env\0",
+            &["ch5_env\0".as_ptr(), "replaced\0".as_ptr(), core::ptr::null::<u8>()],
+            &["FOO=bar\0".as_ptr(), "EMPTY\0".as_ptr(), core::ptr::null::<u8>()],
+        );
+        exit(-1);
+    }
+    let mut xstate: i32 = 0;
+    assert_eq!(waitpid(pid as usize, &mut xstate), pid);
+    assert_eq!(xstate, 0);
+    println!("Test env OK!");
+    0
+}
@@ -0,0 +1,38 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::{exec, exit, fork, waitpid};
+
+/// 测试exec传递的命令行参数。
+/// 正确输出：（无报错信息）
+/// Test exec args OK!
+
+#[no_mangle]
+pub fn main(argc: usize, argv: &[&str]) -> i32 {
+    if argc > 1 {
+        // 被exec之后的子进程，检查参数
+        assert_eq!(argc, 3);
+        assert_eq!(argv, ["ch5_exec_args", "hello", "world!"]);
+        return 0;
+    }
+    let pid = fork();
+    if pid == 0 {
+        exec(
+            "ch5_exec_args\0",
+            &[
+                "ch5_exec_args\0".as_ptr(),
+                "hello\0".as_ptr(),
+                "world!\0".as_ptr(),
+                core::ptr::null::<u8>(),
+            ],
+        );
+        exit(-1);
+    }
+    let mut xstate: i32 = 0;
+    assert_eq!(waitpid(pid as usize, &mut xstate), pid);
+    assert_eq!(xstate, 0);
+    println!("Test exec args OK!");
+    0
+}
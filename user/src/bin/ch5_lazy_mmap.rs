@@ -0,0 +1,80 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::{exit, fork, mmap, munmap, sys_get_time, waitpid, TimeVal};
+
+/// 测试按需分配的 mmap：远大于物理内存的稀疏映射也能成功，第一次访问时才分配清零的页，
+/// 越权访问仍然会杀死进程。
+/// 正确输出：（子进程被杀死时内核打印的 StorePageFault 信息）
+/// Test lazy mmap OK!
+
+const PAGE_SIZE: usize = 4096;
+const START: usize = 0x1_0000_0000;
+// 1GiB，比物理内存大得多
+const LEN: usize = 1 << 30;
+
+fn wait_exit(pid: isize) -> i32 {
+    assert!(pid > 0);
+    let mut xstate: i32 = 0;
+    assert_eq!(waitpid(pid as usize, &mut xstate), pid);
+    xstate
+}
+
+fn page(i: usize) -> *mut usize {
+    (START + i * PAGE_SIZE) as *mut usize
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    assert_eq!(mmap(START, LEN, 3), 0);
+    // 第一次读到的是0，写了之后能读回来
+    let pages = [0, 1, LEN / PAGE_SIZE / 2, LEN / PAGE_SIZE - 1];
+    for (i, &p) in pages.iter().enumerate() {
+        unsafe {
+            assert_eq!(page(p).read_volatile(), 0);
+            page(p).write_volatile(i + 1);
+        }
+    }
+    for (i, &p) in pages.iter().enumerate() {
+        unsafe {
+            assert_eq!(page(p).read_volatile(), i + 1);
+        }
+    }
+    // 内核也可以写还没有分配的页
+    let time = unsafe { &*(page(2) as *const TimeVal) };
+    assert_eq!(sys_get_time(time, 0), 0);
+    assert!(time.sec != 0 || time.usec != 0);
+    // 子进程看得到已经写过的页，没碰过的页还是0
+    let pid = fork();
+    if pid == 0 {
+        for (i, &p) in pages.iter().enumerate() {
+            unsafe {
+                assert_eq!(page(p).read_volatile(), i + 1);
+            }
+        }
+        unsafe {
+            assert_eq!(page(3).read_volatile(), 0);
+        }
+        exit(0);
+    }
+    assert_eq!(wait_exit(pid), 0);
+    assert_eq!(munmap(START, LEN), 0);
+    // 同一段范围可以重新映射，映射为只读时写会杀死进程
+    assert_eq!(mmap(START, PAGE_SIZE, 1), 0);
+    unsafe {
+        assert_eq!(page(0).read_volatile(), 0);
+    }
+    let pid = fork();
+    if pid == 0 {
+        unsafe {
+            page(0).write_volatile(1);
+        }
+        exit(0);
+    }
+    assert_eq!(wait_exit(pid), -2);
+    assert_eq!(munmap(START, PAGE_SIZE), 0);
+    println!("Test lazy mmap OK!");
+    0
+}
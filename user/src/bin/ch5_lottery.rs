@@ -0,0 +1,67 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::{env, exit, fork, get_time, set_tickets, waitpid};
+
+/*
+需要以 SCHED=lottery 编译内核，内核通过环境变量 SCHED 告知当前的调度策略，不是彩票调度时跳过。
+理想结果：3个子进程退出时，count / tickets 基本相同
+*/
+
+fn spin_delay() {
+    let mut j = true;
+    for _ in 0..10 {
+        j = !j;
+    }
+}
+
+const MAX_TIME: isize = 4000;
+const TICKETS: [isize; 3] = [100, 200, 300];
+
+fn count_during(tickets: isize) -> isize {
+    let start_time = get_time();
+    let mut acc = 0;
+    set_tickets(tickets);
+    loop {
+        spin_delay();
+        acc += 1;
+        if acc % 400 == 0 {
+            let time = get_time() - start_time;
+            if time > MAX_TIME {
+                return acc;
+            }
+        }
+    }
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    if env::var("SCHED") != Some("lottery") {
+        println!("lottery scheduler is not in use, skipped");
+        return 0;
+    }
+    // 父进程等待期间几乎不占用彩票
+    set_tickets(1);
+    let mut pid = [0; 3];
+    for (i, &tickets) in TICKETS.iter().enumerate() {
+        pid[i] = fork();
+        if pid[i] == 0 {
+            exit((count_during(tickets) / tickets) as i32);
+        }
+    }
+    let mut ratio = [0; 3];
+    for i in 0..3 {
+        let mut xstate: i32 = 0;
+        assert_eq!(waitpid(pid[i] as usize, &mut xstate), pid[i]);
+        ratio[i] = xstate as isize;
+        println!("tickets = {}, ratio = {}", TICKETS[i], ratio[i]);
+    }
+    let max = *ratio.iter().max().unwrap();
+    let min = *ratio.iter().min().unwrap();
+    // 允许一定的随机误差
+    assert!(max * 2 < min * 3, "share is not proportional to tickets");
+    println!("Test lottery OK!");
+    0
+}
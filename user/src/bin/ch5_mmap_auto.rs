@@ -0,0 +1,42 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::{mmap, munmap, MMAP_HINT};
+
+/// 测试由内核选地址的 mmap。
+/// 正确输出：（无报错信息）
+/// Test mmap auto OK!
+
+const PAGE_SIZE: usize = 4096;
+
+#[no_mangle]
+pub fn main() -> i32 {
+    // start为0时返回选中的地址，几次映射互不重叠
+    let a = mmap(0, PAGE_SIZE * 2, 3);
+    assert!(a > 0 && a as usize % PAGE_SIZE == 0);
+    let b = mmap(0, PAGE_SIZE, 3);
+    assert!(b > 0 && (b >= a + PAGE_SIZE as isize * 2 || b + PAGE_SIZE as isize <= a));
+    unsafe {
+        (a as *mut usize).write_volatile(1);
+        ((a as usize + PAGE_SIZE) as *mut usize).write_volatile(2);
+        (b as *mut usize).write_volatile(3);
+        assert_eq!((a as *const usize).read_volatile(), 1);
+    }
+    // 释放后留下的空隙可以再用
+    assert_eq!(munmap(a as usize, PAGE_SIZE * 2), 0);
+    let c = mmap(0, PAGE_SIZE, 1);
+    assert_eq!(c, a);
+    // 空闲的参考地址直接用，被占用时另选
+    let hint = 0x2000_0000;
+    assert_eq!(mmap(hint, PAGE_SIZE, 3 | MMAP_HINT), hint as isize);
+    let d = mmap(hint, PAGE_SIZE, 3 | MMAP_HINT);
+    assert!(d > 0 && d != hint as isize);
+    // 固定地址的映射仍然返回0
+    assert_eq!(mmap(0x3000_0000, PAGE_SIZE, 3), 0);
+    assert_eq!(mmap(0x3000_0000, PAGE_SIZE, 3), -1);
+    assert_eq!(mmap(0, 0, 3), -1);
+    println!("Test mmap auto OK!");
+    0
+}
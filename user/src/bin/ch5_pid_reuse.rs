@@ -0,0 +1,35 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::{exit, fork, waitpid};
+
+/// 测试刚退出的子进程的pid不会马上分给新的子进程。
+/// 正确输出：（无报错信息）
+/// Test pid reuse OK!
+
+const ROUNDS: usize = 50;
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let mut last_pid = 0;
+    for i in 0..ROUNDS {
+        let pid = fork();
+        if pid == 0 {
+            exit(i as i32);
+        }
+        assert!(pid > 0);
+        let mut xstate: i32 = 0;
+        assert_eq!(waitpid(pid as usize, &mut xstate), pid);
+        assert_eq!(xstate, i as i32);
+        // 上一个子进程已经被回收，新的子进程也不能拿到它的pid
+        assert!(pid as usize != last_pid);
+        if i > 0 {
+            assert!(pid as usize > last_pid);
+        }
+        last_pid = pid as usize;
+    }
+    println!("Test pid reuse OK!");
+    0
+}
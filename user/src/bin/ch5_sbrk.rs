@@ -0,0 +1,50 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+extern crate alloc;
+
+use alloc::vec::Vec;
+use user_lib::{brk, sbrk};
+
+/// 测试 brk/sbrk 和随之增长的用户堆。
+/// 正确输出：（无报错信息）
+/// Test sbrk OK!
+
+const PAGE_SIZE: usize = 4096;
+
+#[no_mangle]
+pub fn main() -> i32 {
+    // 直接调整堆，新的页读出来是0
+    let old = sbrk(0);
+    assert!(old > 0);
+    assert_eq!(sbrk(PAGE_SIZE as isize * 2), old);
+    assert_eq!(brk(0), old + PAGE_SIZE as isize * 2);
+    let p = old as *mut usize;
+    unsafe {
+        assert_eq!(p.read_volatile(), 0);
+        p.write_volatile(0x1234);
+        assert_eq!(p.read_volatile(), 0x1234);
+    }
+    // 缩小后再扩大，释放过的页又是0
+    assert_eq!(sbrk(-(PAGE_SIZE as isize * 2)), old + PAGE_SIZE as isize * 2);
+    assert_eq!(brk(0), old);
+    assert_eq!(sbrk(PAGE_SIZE as isize), old);
+    unsafe {
+        assert_eq!(p.read_volatile(), 0);
+    }
+    // 不能缩到堆的起点下面
+    assert_eq!(brk(PAGE_SIZE), old + PAGE_SIZE as isize);
+    // 远超静态堆大小的分配
+    let mut v: Vec<usize> = Vec::new();
+    for i in 0..64 * 1024 {
+        v.push(i);
+    }
+    for (i, x) in v.iter().enumerate() {
+        assert_eq!(*x, i);
+    }
+    assert!(brk(0) > old + PAGE_SIZE as isize);
+    println!("Test sbrk OK!");
+    0
+}
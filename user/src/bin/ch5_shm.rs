@@ -0,0 +1,69 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::{exit, fork, munmap, shm_open, spawn_with, waitpid, SpawnAttr};
+
+/// 测试共享内存：fork 之后仍然共享，不相关的进程用相同的键值映射到同一块内存。
+/// 正确输出：（无报错信息）
+/// Test shm OK!
+
+const PAGE_SIZE: usize = 4096;
+const KEY: usize = 0x5348_4d;
+const COUNT: usize = 100;
+
+fn wait_ok(pid: isize) {
+    assert!(pid > 0);
+    let mut xstate: i32 = 0;
+    assert_eq!(waitpid(pid as usize, &mut xstate), pid);
+    assert_eq!(xstate, 0);
+}
+
+fn slot(base: isize, i: usize) -> *mut usize {
+    (base as usize + i * core::mem::size_of::<usize>()) as *mut usize
+}
+
+#[no_mangle]
+pub fn main(argc: usize, _argv: &[&str]) -> i32 {
+    if argc > 1 {
+        // 生产者，通过键值打开父进程建好的共享内存，写入数据后置位标志
+        let base = shm_open(KEY, PAGE_SIZE * 2, 3);
+        assert!(base > 0);
+        unsafe {
+            for i in 1..=COUNT {
+                slot(base, i).write_volatile(i * i);
+            }
+            slot(base, 0).write_volatile(1);
+        }
+        return 0;
+    }
+    // 匿名的共享内存，fork之后子进程的写父进程看得到
+    let anon = shm_open(0, PAGE_SIZE, 3);
+    assert!(anon > 0);
+    let pid = fork();
+    if pid == 0 {
+        unsafe {
+            slot(anon, 0).write_volatile(42);
+        }
+        exit(0);
+    }
+    wait_ok(pid);
+    assert_eq!(unsafe { slot(anon, 0).read_volatile() }, 42);
+    assert_eq!(munmap(anon as usize, PAGE_SIZE), 0);
+    // 按键值共享，长度不同时打不开
+    let base = shm_open(KEY, PAGE_SIZE * 2, 3);
+    assert!(base > 0);
+    assert_eq!(shm_open(KEY, PAGE_SIZE, 3), -1);
+    let argv = ["ch5_shm\0".as_ptr(), "producer\0".as_ptr(), core::ptr::null::<u8>()];
+    let pid = spawn_with("ch5_shm\0", &argv, &SpawnAttr::default()).unwrap();
+    wait_ok(pid as isize);
+    unsafe {
+        assert_eq!(slot(base, 0).read_volatile(), 1);
+        for i in 1..=COUNT {
+            assert_eq!(slot(base, i).read_volatile(), i * i);
+        }
+    }
+    println!("Test shm OK!");
+    0
+}
@@ -0,0 +1,35 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::{exit, fork, get_time, sleep_blocking, waitpid};
+
+/// 正确输出：（无报错信息）
+/// Test sleep_blocking OK!
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let start = get_time();
+    sleep_blocking(100);
+    let delta = get_time() - start;
+    assert!(delta >= 100, "woke up too early, delta = {}ms", delta);
+    // 多个进程睡眠不同的时长，按到期时刻先后醒来
+    let mut pid = [0; 3];
+    for (i, &ms) in [300usize, 100, 200].iter().enumerate() {
+        pid[i] = fork();
+        if pid[i] == 0 {
+            sleep_blocking(ms);
+            exit((get_time() - start) as i32);
+        }
+    }
+    let mut woke = [0; 3];
+    for i in 0..3 {
+        let mut xstate: i32 = 0;
+        assert_eq!(waitpid(pid[i] as usize, &mut xstate), pid[i]);
+        woke[i] = xstate;
+    }
+    assert!(woke[1] <= woke[2] && woke[2] <= woke[0]);
+    println!("Test sleep_blocking OK!");
+    0
+}
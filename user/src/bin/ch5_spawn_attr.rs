@@ -0,0 +1,61 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::{env, spawn_with, waitpid, SpawnAttr, SpawnError};
+
+/// 测试带参数和属性的 spawn 以及它的错误码。
+/// 正确输出：（无报错信息）
+/// Test spawn attr OK!
+
+fn wait_ok(pid: usize) {
+    let mut xstate: i32 = 0;
+    assert_eq!(waitpid(pid, &mut xstate), pid as isize);
+    assert_eq!(xstate, 0);
+}
+
+#[no_mangle]
+pub fn main(argc: usize, argv: &[&str]) -> i32 {
+    if argc > 1 {
+        // 子进程，检查参数和环境变量
+        assert_eq!(argv, ["ch5_spawn_attr", "child"]);
+        assert_eq!(env::var("MODE"), Some("child"));
+        return 0;
+    }
+    let argv = [
+        "ch5_spawn_attr\0".as_ptr(),
+        "child\0".as_ptr(),
+        core::ptr::null::<u8>(),
+    ];
+    let envs = ["MODE=child\0".as_ptr(), core::ptr::null::<u8>()];
+    // 参数、环境变量、优先级、内核栈大小和用户栈的上限
+    let attr = SpawnAttr::default()
+        .priority(8)
+        .kernel_stack_size(4096 * 4)
+        .stack_limit(4096 * 16)
+        .envs(&envs);
+    wait_ok(spawn_with("ch5_spawn_attr\0", &argv, &attr).unwrap());
+    // 各种错误
+    assert_eq!(
+        spawn_with("no_such_app\0", &argv, &attr),
+        Err(SpawnError::NotFound)
+    );
+    let bad_prio = SpawnAttr::default().priority(1);
+    assert_eq!(
+        spawn_with("ch5_spawn_attr\0", &argv, &bad_prio),
+        Err(SpawnError::Invalid)
+    );
+    let bad_stack = SpawnAttr::default().kernel_stack_size(100);
+    assert_eq!(
+        spawn_with("ch5_spawn_attr\0", &argv, &bad_stack),
+        Err(SpawnError::Invalid)
+    );
+    let bad_limit = SpawnAttr::default().stack_limit(100);
+    assert_eq!(
+        spawn_with("ch5_spawn_attr\0", &argv, &bad_limit),
+        Err(SpawnError::Invalid)
+    );
+    println!("Test spawn attr OK!");
+    0
+}
@@ -0,0 +1,44 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::{exit, fork, waitpid};
+
+/// 测试用户栈的自动增长：比初始栈深得多的递归可以正常完成，无限递归被当作栈溢出杀死。
+/// 正确输出：（子进程被杀死时内核打印的 Stack overflow 信息）
+/// Test stack grow OK!
+
+const FRAME: usize = 1024;
+
+// 每层在栈上占用FRAME字节，返回各层记下的值之和
+fn recurse(n: usize, limit: usize) -> usize {
+    let mut buf = [0u8; FRAME];
+    unsafe {
+        core::ptr::write_volatile(&mut buf[0], n as u8);
+    }
+    if n == limit {
+        return 0;
+    }
+    let sum = recurse(n + 1, limit);
+    sum + unsafe { core::ptr::read_volatile(&buf[0]) } as usize
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    // 大约1MiB的栈
+    let depth = 1000;
+    let expected: usize = (0..depth).map(|n| n as u8 as usize).sum();
+    assert_eq!(recurse(0, depth), expected);
+    // 无限递归撞上保护区
+    let pid = fork();
+    if pid == 0 {
+        recurse(0, usize::MAX);
+        exit(0);
+    }
+    let mut xstate: i32 = 0;
+    assert_eq!(waitpid(pid as usize, &mut xstate), pid);
+    assert_eq!(xstate, -2);
+    println!("Test stack grow OK!");
+    0
+}
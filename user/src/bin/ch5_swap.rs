@@ -0,0 +1,41 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::{mmap, munmap};
+
+/// 测试页面置换：映射并写满比物理内存还大的范围，换出的页读回来内容不变。
+/// 需要交换区，qemu 要给出 256M 内存。
+/// 正确输出：（无报错信息）
+/// Test swap OK!
+
+const PAGE_SIZE: usize = 4096;
+// 160MiB，比MEMORY_END以下能分配的页帧多
+const PAGES: usize = 160 * 256;
+
+fn page(start: usize, i: usize) -> *mut usize {
+    (start + i * PAGE_SIZE) as *mut usize
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    // 做两次，munmap之后页帧和交换区的槽位都要还回去
+    for round in 0..2 {
+        let start = mmap(0, PAGES * PAGE_SIZE, 3);
+        assert!(start > 0);
+        let start = start as usize;
+        for i in 0..PAGES {
+            unsafe { page(start, i).write_volatile(i + round) };
+        }
+        // 读两遍，第二遍读的页大多是上一遍刚换入又被换出的
+        for _ in 0..2 {
+            for i in 0..PAGES {
+                assert_eq!(unsafe { page(start, i).read_volatile() }, i + round);
+            }
+        }
+        assert_eq!(munmap(start, PAGES * PAGE_SIZE), 0);
+    }
+    println!("Test swap OK!");
+    0
+}
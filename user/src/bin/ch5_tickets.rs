@@ -0,0 +1,41 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::{exit, fork, set_tickets, transfer_tickets, waitpid, yield_};
+
+/// 正确输出：（无报错信息）
+/// Test tickets OK!
+
+#[no_mangle]
+pub fn main() -> i32 {
+    assert_eq!(set_tickets(0), -1);
+    assert_eq!(set_tickets(-10), -1);
+    assert_eq!(set_tickets(100), 100);
+    // 不能借给不是自己子进程的进程
+    assert_eq!(transfer_tickets(0, 10), -1);
+    let pid = fork();
+    if pid == 0 {
+        for _ in 0..10 {
+            yield_();
+        }
+        exit(0);
+    }
+    // 至少要给自己留一张
+    assert_eq!(transfer_tickets(pid as usize, 0), -1);
+    assert_eq!(transfer_tickets(pid as usize, 100), -1);
+    // 子进程继承了100张，再借给它40张
+    assert_eq!(transfer_tickets(pid as usize, 40), 140);
+    let mut xstate: i32 = 0;
+    assert_eq!(waitpid(pid as usize, &mut xstate), pid);
+    // 子进程退出后归还，自己又有100张，新的子进程继承100张后还能再借99张
+    let pid = fork();
+    if pid == 0 {
+        exit(0);
+    }
+    assert_eq!(transfer_tickets(pid as usize, 99), 199);
+    assert_eq!(waitpid(pid as usize, &mut xstate), pid);
+    println!("Test tickets OK!");
+    0
+}
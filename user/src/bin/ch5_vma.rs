@@ -0,0 +1,90 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::{exit, fork, mmap, mprotect, munmap, waitpid};
+
+/// 测试部分 munmap、mprotect 以及逻辑段的拆分与合并。
+/// 正确输出：（子进程被杀死时内核打印的 PageFault 信息）
+/// Test vma OK!
+
+const PAGE_SIZE: usize = 4096;
+const START: usize = 0x1000_0000;
+
+fn page(i: usize) -> *mut usize {
+    (START + i * PAGE_SIZE) as *mut usize
+}
+
+fn read(i: usize) -> usize {
+    unsafe { page(i).read_volatile() }
+}
+
+fn write(i: usize, value: usize) {
+    unsafe { page(i).write_volatile(value) }
+}
+
+// 在子进程里做f，返回子进程的退出码
+fn in_child(f: fn()) -> i32 {
+    let pid = fork();
+    if pid == 0 {
+        f();
+        exit(0);
+    }
+    let mut xstate: i32 = 0;
+    assert_eq!(waitpid(pid as usize, &mut xstate), pid);
+    xstate
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    assert_eq!(mmap(START, PAGE_SIZE * 4, 3), 0);
+    for i in 0..4 {
+        write(i, i + 1);
+    }
+    // 释放中间的一页，两边的页不受影响
+    assert_eq!(munmap(START + PAGE_SIZE, PAGE_SIZE), 0);
+    assert_eq!(in_child(|| write(1, 0)), -2);
+    assert_eq!(read(0), 1);
+    assert_eq!(read(2), 3);
+    // mprotect的范围里有空洞时失败，munmap的首尾落在空洞里时失败，未对齐时也失败
+    assert_eq!(mprotect(START, PAGE_SIZE * 3, 1), -1);
+    assert_eq!(munmap(START + PAGE_SIZE, PAGE_SIZE * 2), -1);
+    assert_eq!(munmap(START, PAGE_SIZE * 2), -1);
+    assert_eq!(munmap(START + 1, PAGE_SIZE), -1);
+    // 释放掉的范围可以重新映射，新的页是0
+    assert_eq!(mmap(START + PAGE_SIZE, PAGE_SIZE, 3), 0);
+    assert_eq!(read(1), 0);
+    // 只读的页写不了，读还可以，改回可写后又能写
+    assert_eq!(mprotect(START + PAGE_SIZE * 2, PAGE_SIZE, 1), 0);
+    assert_eq!(in_child(|| write(2, 0)), -2);
+    assert_eq!(read(2), 3);
+    assert_eq!(mprotect(START + PAGE_SIZE * 2, PAGE_SIZE, 3), 0);
+    write(2, 30);
+    assert_eq!(read(2), 30);
+    // 写时复制的页改成可写之后仍然先复制
+    assert_eq!(
+        in_child(|| {
+            assert_eq!(mprotect(START, PAGE_SIZE * 4, 1), 0);
+            assert_eq!(mprotect(START, PAGE_SIZE * 4, 3), 0);
+            write(3, 40);
+            assert_eq!(read(3), 40);
+        }),
+        0
+    );
+    assert_eq!(read(3), 4);
+    // 跨过相邻的两次映射释放
+    assert_eq!(mmap(START + PAGE_SIZE * 4, PAGE_SIZE, 3), 0);
+    write(4, 5);
+    assert_eq!(munmap(START + PAGE_SIZE * 2, PAGE_SIZE * 3), 0);
+    assert_eq!(in_child(|| write(4, 0)), -2);
+    assert_eq!(read(0), 1);
+    // 跨过空洞释放，空洞两边的映射都释放掉
+    assert_eq!(mmap(START + PAGE_SIZE * 3, PAGE_SIZE, 3), 0);
+    assert_eq!(munmap(START, PAGE_SIZE * 4), 0);
+    assert_eq!(in_child(|| write(0, 0)), -2);
+    assert_eq!(in_child(|| write(3, 0)), -2);
+    assert_eq!(mprotect(START, PAGE_SIZE, 3), -1);
+    println!("Test vma OK!");
+    0
+}
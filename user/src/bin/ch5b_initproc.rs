@@ -4,19 +4,25 @@
 #[macro_use]
 extern crate user_lib;
 
-use user_lib::{exec, fork, wait, yield_};
+use user_lib::{exec, fork, wait};
 
 #[no_mangle]
 fn main() -> i32 {
-    if fork() == 0 {
+    let shell = fork();
+    if shell == 0 {
         exec("ch5b_user_shell\0", &[0 as *const u8]);
     } else {
+        // shell的退出码，所有进程都退出后作为自己的退出码，内核带着它关机
+        let mut shell_exit_code: i32 = 0;
         loop {
             let mut exit_code: i32 = 0;
             let pid = wait(&mut exit_code);
+            // 没有子进程了，收养的孤儿进程也都已退出
             if pid == -1 {
-                yield_();
-                continue;
+                return shell_exit_code;
+            }
+            if pid == shell {
+                shell_exit_code = exit_code;
             }
             println!(
                 "[initproc] Released a zombie process, pid={}, exit_code={}",
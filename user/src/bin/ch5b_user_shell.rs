@@ -12,13 +12,16 @@ const DL: u8 = 0x7fu8;
 const BS: u8 = 0x08u8;
 
 use alloc::string::String;
+use alloc::vec::Vec;
 use user_lib::console::getchar;
-use user_lib::{exec, flush, fork, waitpid};
+use user_lib::{flush, spawn_with, waitpid, SpawnAttr, SpawnError};
 
 #[no_mangle]
 pub fn main() -> i32 {
     println!("Rust user shell");
     let mut line: String = String::new();
+    // 上一个命令的退出码，exit不带参数时作为shell的退出码
+    let mut last_exit_code: i32 = 0;
     print!(">> ");
     flush();
     loop {
@@ -27,20 +30,43 @@ pub fn main() -> i32 {
             LF | CR => {
                 print!("\n");
                 if !line.is_empty() {
-                    line.push('\0');
-                    let pid = fork();
-                    if pid == 0 {
-                        // child process
-                        if exec(line.as_str(), &[0 as *const u8]) == -1 {
-                            println!("Error when executing!");
-                            return -4;
+                    // 按空白切分出应用名和参数，argv[0]是应用名
+                    let args: Vec<String> = line
+                        .split_whitespace()
+                        .map(|arg| {
+                            let mut arg = String::from(arg);
+                            arg.push('\0');
+                            arg
+                        })
+                        .collect();
+                    let mut argv: Vec<*const u8> = args.iter().map(|arg| arg.as_ptr()).collect();
+                    argv.push(core::ptr::null());
+                    // 只有空白时什么都不做
+                    if let Some(name) = args.first() {
+                        // 内建的exit命令，退出shell，用户初始进程随后带着这个退出码关机
+                        if name == "exit\0" {
+                            return match args.get(1) {
+                                Some(code) => code.trim_end_matches('\0').parse().unwrap_or(-1),
+                                None => last_exit_code,
+                            };
+                        }
+                        match spawn_with(name.as_str(), &argv, &SpawnAttr::default()) {
+                            Ok(pid) => {
+                                let mut exit_code: i32 = 0;
+                                let exit_pid = waitpid(pid, &mut exit_code);
+                                assert_eq!(pid as isize, exit_pid);
+                                last_exit_code = exit_code;
+                                println!("Shell: Process {} exited with code {}", pid, exit_code);
+                            }
+                            Err(SpawnError::NotFound) => {
+                                println!("{}: command not found", name.trim_end_matches('\0'));
+                                last_exit_code = -1;
+                            }
+                            Err(err) => {
+                                println!("Error when executing: {:?}", err);
+                                last_exit_code = -1;
+                            }
                         }
-                        unreachable!();
-                    } else {
-                        let mut exit_code: i32 = 0;
-                        let exit_pid = waitpid(pid as usize, &mut exit_code);
-                        assert_eq!(pid, exit_pid);
-                        println!("Shell: Process {} exited with code {}", pid, exit_code);
                     }
                     line.clear();
                 }
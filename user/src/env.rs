@@ -0,0 +1,83 @@
+//! 环境变量和辅助向量，由内核放在初始用户栈上，_start 时记录下位置。
+//! 内核没有提供时（a2/a3 为 0）视为空。
+
+pub const AT_NULL: usize = 0;
+pub const AT_PHDR: usize = 3;
+pub const AT_PAGESZ: usize = 6;
+pub const AT_ENTRY: usize = 9;
+/// 时钟计数器的频率，和 get_time 读到的计数一致
+pub const AT_CLKTCK: usize = 17;
+/// 16 个随机字节的地址
+pub const AT_RANDOM: usize = 25;
+
+static mut ENVP: usize = 0;
+static mut AUXV: usize = 0;
+
+pub(crate) fn init(envp: usize, auxv: usize) {
+    unsafe {
+        ENVP = envp;
+        AUXV = auxv;
+    }
+}
+
+unsafe fn cstr(start: usize) -> &'static str {
+    let len = (0usize..)
+        .find(|i| ((start + *i) as *const u8).read_volatile() == 0)
+        .unwrap();
+    core::str::from_utf8(core::slice::from_raw_parts(start as *const u8, len)).unwrap()
+}
+
+/// 所有环境变量，形如 (KEY, VALUE)，没有 `=` 的项值为空串
+pub fn vars() -> impl Iterator<Item = (&'static str, &'static str)> {
+    let envp = unsafe { ENVP };
+    (0usize..)
+        .map(move |i| {
+            if envp == 0 {
+                0
+            } else {
+                unsafe { ((envp + i * core::mem::size_of::<usize>()) as *const usize).read_volatile() }
+            }
+        })
+        .take_while(|&ptr| ptr != 0)
+        .map(|ptr| {
+            let entry = unsafe { cstr(ptr) };
+            match entry.find('=') {
+                Some(pos) => (&entry[..pos], &entry[pos + 1..]),
+                None => (entry, ""),
+            }
+        })
+}
+
+/// 查找环境变量 key 的值
+pub fn var(key: &str) -> Option<&'static str> {
+    vars().find(|(k, _)| *k == key).map(|(_, v)| v)
+}
+
+/// 查找辅助向量中类型为 tag 的值
+pub fn aux(tag: usize) -> Option<usize> {
+    let auxv = unsafe { AUXV };
+    if auxv == 0 {
+        return None;
+    }
+    let word = core::mem::size_of::<usize>();
+    for i in 0.. {
+        let (key, value) = unsafe {
+            (
+                ((auxv + 2 * i * word) as *const usize).read_volatile(),
+                ((auxv + (2 * i + 1) * word) as *const usize).read_volatile(),
+            )
+        };
+        if key == AT_NULL {
+            break;
+        }
+        if key == tag {
+            return Some(value);
+        }
+    }
+    None
+}
+
+/// 内核提供的 16 个随机字节
+pub fn random_bytes() -> Option<[u8; 16]> {
+    aux(AT_RANDOM).map(|addr| unsafe { (addr as *const [u8; 16]).read_volatile() })
+}
@@ -5,6 +5,7 @@
 
 #[macro_use]
 pub mod console;
+pub mod env;
 mod lang_items;
 mod syscall;
 
@@ -15,15 +16,42 @@ extern crate bitflags;
 
 use alloc::vec::Vec;
 use buddy_system_allocator::LockedHeap;
+use core::alloc::{GlobalAlloc, Layout};
 pub use console::{flush, STDIN, STDOUT};
 pub use syscall::*;
 
 const USER_HEAP_SIZE: usize = 16384;
+/// 静态的堆用完之后，每次至少通过 sbrk 再要这么多
+const USER_HEAP_GROW_SIZE: usize = 16384;
 
 static mut HEAP_SPACE: [u8; USER_HEAP_SIZE] = [0; USER_HEAP_SIZE];
 
 #[global_allocator]
-static HEAP: LockedHeap = LockedHeap::empty();
+static HEAP: GrowableHeap = GrowableHeap(LockedHeap::empty());
+
+/// 先用静态数组里的堆，不够时通过 sbrk 向内核要更多的空间
+struct GrowableHeap(LockedHeap);
+
+unsafe impl GlobalAlloc for GrowableHeap {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = self.0.alloc(layout);
+        if !ptr.is_null() {
+            return ptr;
+        }
+        // 要两倍大小，新的空间里才一定有按大小对齐的一块
+        let size = layout.size().max(layout.align()).next_power_of_two() * 2;
+        let size = size.max(USER_HEAP_GROW_SIZE);
+        let start = sbrk(size as isize);
+        if start == -1 {
+            return ptr;
+        }
+        self.0.lock().add_to_heap(start as usize, start as usize + size);
+        self.0.alloc(layout)
+    }
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        self.0.dealloc(ptr, layout)
+    }
+}
 
 #[alloc_error_handler]
 pub fn handle_alloc_error(layout: core::alloc::Layout) -> ! {
@@ -46,10 +74,12 @@ fn clear_bss() {
 
 #[no_mangle]
 #[link_section = ".text.entry"]
-pub extern "C" fn _start(argc: usize, argv: usize) -> ! {
+pub extern "C" fn _start(argc: usize, argv: usize, envp: usize, auxv: usize) -> ! {
     clear_bss();
+    env::init(envp, auxv);
     unsafe {
-        HEAP.lock()
+        HEAP.0
+            .lock()
             .init(HEAP_SPACE.as_ptr() as usize, USER_HEAP_SIZE);
     }
     let mut v: Vec<&'static str> = Vec::new();
@@ -131,6 +161,90 @@ impl TaskInfo {
     }
 }
 
+pub const SPAWN_SETPRIORITY: usize = 1 << 0;
+pub const SPAWN_SETTICKETS: usize = 1 << 1;
+pub const SPAWN_SETKSTACK: usize = 1 << 2;
+pub const SPAWN_SETENV: usize = 1 << 3;
+pub const SPAWN_SETSTACK: usize = 1 << 4;
+
+/// spawn_with 的属性，flags 中对应的位置位时相应的字段才有效
+#[repr(C)]
+#[derive(Debug, Default)]
+pub struct SpawnAttr {
+    pub flags: usize,
+    /// 初始优先级，至少为 2
+    pub priority: isize,
+    /// 初始彩票数，至少为 1
+    pub tickets: isize,
+    /// 内核栈大小，按页对齐
+    pub kernel_stack_size: usize,
+    /// 以空指针结尾的环境变量数组，不设置时继承父进程的环境变量
+    pub envp: usize,
+    /// 用户栈最多能增长到的大小，按页对齐
+    pub stack_limit: usize,
+}
+
+impl SpawnAttr {
+    pub fn priority(mut self, priority: isize) -> Self {
+        self.flags |= SPAWN_SETPRIORITY;
+        self.priority = priority;
+        self
+    }
+    pub fn tickets(mut self, tickets: isize) -> Self {
+        self.flags |= SPAWN_SETTICKETS;
+        self.tickets = tickets;
+        self
+    }
+    pub fn kernel_stack_size(mut self, size: usize) -> Self {
+        self.flags |= SPAWN_SETKSTACK;
+        self.kernel_stack_size = size;
+        self
+    }
+    pub fn envs(mut self, envs: &[*const u8]) -> Self {
+        self.flags |= SPAWN_SETENV;
+        self.envp = envs.as_ptr() as usize;
+        self
+    }
+    pub fn stack_limit(mut self, limit: usize) -> Self {
+        self.flags |= SPAWN_SETSTACK;
+        self.stack_limit = limit;
+        self
+    }
+}
+
+/// spawn_with 失败的原因，内核返回的是 Linux 中对应 errno 的相反数
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum SpawnError {
+    /// 没有这个名字的应用
+    NotFound,
+    /// 参数和环境变量太长
+    TooBig,
+    /// 应用装载不了
+    NoExec,
+    /// 内存不够
+    OutOfMemory,
+    /// 路径、参数或属性所在的内存访问不了
+    Fault,
+    /// 属性不合法
+    Invalid,
+    /// 其他错误码
+    Other(isize),
+}
+
+impl SpawnError {
+    fn from_code(code: isize) -> Self {
+        match code {
+            -2 => SpawnError::NotFound,
+            -7 => SpawnError::TooBig,
+            -8 => SpawnError::NoExec,
+            -12 => SpawnError::OutOfMemory,
+            -14 => SpawnError::Fault,
+            -22 => SpawnError::Invalid,
+            _ => SpawnError::Other(code),
+        }
+    }
+}
+
 #[repr(C)]
 #[derive(Debug)]
 pub struct Stat {
@@ -240,14 +354,43 @@ pub fn fork() -> isize {
     sys_fork()
 }
 
+pub const CLONE_VM: usize = 0x100;
+pub const CLONE_VFORK: usize = 0x4000;
+
+/// 新建子进程，它在 stack 栈顶上调用 f(arg)，f 返回后以返回值退出。
+/// stack 必须 16 字节对齐且不能为 0，成功时父进程得到子进程的 pid
+pub fn clone(f: extern "C" fn(usize) -> i32, stack: usize, flags: usize, arg: usize) -> isize {
+    unsafe { __clone(f, stack, flags, arg) }
+}
+
+/// 和父进程共享地址空间，父进程阻塞到子进程 exec 或退出为止。
+/// 子进程借用父进程的栈，只能接着调用 exec 或 exit，不能从调用 vfork 的函数返回
+#[inline(always)]
+pub fn vfork() -> isize {
+    unsafe { __vfork() }
+}
+
 pub fn exec(path: &str, args: &[*const u8]) -> isize {
     sys_exec(path, args)
 }
 
+/// 和 exec 一样，但用 envs 替换环境变量，envs 同样以空指针结尾
+pub fn execve(path: &str, args: &[*const u8], envs: &[*const u8]) -> isize {
+    sys_execve(path, args, envs)
+}
+
 pub fn set_priority(prio: isize) -> isize {
     sys_set_priority(prio)
 }
 
+pub fn set_tickets(tickets: isize) -> isize {
+    sys_set_tickets(tickets)
+}
+
+pub fn transfer_tickets(pid: usize, tickets: usize) -> isize {
+    sys_transfer_tickets(pid, tickets)
+}
+
 pub fn wait(exit_code: &mut i32) -> isize {
     loop {
         match sys_waitpid(-1, exit_code as *mut _) {
@@ -284,6 +427,11 @@ pub fn sleep(period_ms: usize) {
         sys_yield();
     }
 }
+/// prot 带上这一位时 start 只是参考，由内核选地址
+pub const MMAP_HINT: usize = 1 << 8;
+
+/// start 为 0 或者 prot 带有 MMAP_HINT 时由内核选地址，返回选中的地址；
+/// 否则映射在 start 处，成功时返回 0
 pub fn mmap(start: usize, len: usize, prot: usize) -> isize {
     sys_mmap(start, len, prot)
 }
@@ -292,10 +440,44 @@ pub fn munmap(start: usize, len: usize) -> isize {
     sys_munmap(start, len)
 }
 
+/// 打开键值为 key、长度为 len 字节的共享内存并映射进来，返回映射的地址，失败时返回 -1；
+/// key 相同的进程映射到同一组物理页帧，key 为 0 时新建一块只能通过 fork 共享的内存
+pub fn shm_open(key: usize, len: usize, prot: usize) -> isize {
+    sys_shm_open(key, len, prot)
+}
+
+/// 修改 [start, start + len) 的访问权限，prot 的含义和 mmap 一样
+pub fn mprotect(start: usize, len: usize, prot: usize) -> isize {
+    sys_mprotect(start, len, prot)
+}
+
+/// 把堆的结束地址调整到 addr，返回调整后的结束地址，addr 为 0 时只查询
+pub fn brk(addr: usize) -> isize {
+    sys_brk(addr)
+}
+
+/// 把堆扩大 increment 字节（可以为负），返回原来的结束地址，失败时返回 -1
+pub fn sbrk(increment: isize) -> isize {
+    let old = sys_brk(0);
+    let new = old.wrapping_add(increment) as usize;
+    if increment != 0 && sys_brk(new) != new as isize {
+        return -1;
+    }
+    old
+}
+
 pub fn spawn(path: &str) -> isize {
     sys_spawn(path)
 }
 
+/// 带参数和属性的 spawn，args 以空指针结尾，成功时返回子进程的 pid
+pub fn spawn_with(path: &str, args: &[*const u8], attr: &SpawnAttr) -> Result<usize, SpawnError> {
+    match sys_spawn_with(path, args, attr) {
+        pid if pid >= 0 => Ok(pid as usize),
+        code => Err(SpawnError::from_code(code)),
+    }
+}
+
 pub fn dup(fd: usize) -> isize {
     sys_dup(fd)
 }
@@ -1,6 +1,6 @@
 use crate::TaskInfo;
 
-use super::{Stat, TimeVal};
+use super::{SpawnAttr, Stat, TimeVal};
 
 pub const SYSCALL_OPENAT: usize = 56;
 pub const SYSCALL_CLOSE: usize = 57;
@@ -19,9 +19,15 @@ pub const SYSCALL_FORK: usize = 220;
 pub const SYSCALL_EXEC: usize = 221;
 pub const SYSCALL_WAITPID: usize = 260;
 pub const SYSCALL_SET_PRIORITY: usize = 140;
+pub const SYSCALL_SET_TICKETS: usize = 141;
+pub const SYSCALL_TRANSFER_TICKETS: usize = 142;
+pub const SYSCALL_BRK: usize = 214;
 pub const SYSCALL_MUNMAP: usize = 215;
 pub const SYSCALL_MMAP: usize = 222;
+pub const SYSCALL_MPROTECT: usize = 226;
 pub const SYSCALL_SPAWN: usize = 400;
+pub const SYSCALL_CLONE: usize = 403;
+pub const SYSCALL_SHM_OPEN: usize = 404;
 pub const SYSCALL_MAIL_READ: usize = 401;
 pub const SYSCALL_MAIL_WRITE: usize = 402;
 pub const SYSCALL_DUP: usize = 24;
@@ -173,6 +179,69 @@ pub fn sys_exec(path: &str, args: &[*const u8]) -> isize {
     )
 }
 
+pub fn sys_execve(path: &str, args: &[*const u8], envs: &[*const u8]) -> isize {
+    syscall(
+        SYSCALL_EXEC,
+        [
+            path.as_ptr() as usize,
+            args.as_ptr() as usize,
+            envs.as_ptr() as usize,
+        ],
+    )
+}
+
+pub fn sys_clone(flags: usize, stack: usize) -> isize {
+    syscall(SYSCALL_CLONE, [flags, stack, 0])
+}
+
+// clone 出来的子进程换了栈，vfork 出来的子进程会覆盖父进程之后还要用的栈帧，
+// 两者都不能从 Rust 函数里返回，在汇编里发起调用
+core::arch::global_asm!(
+    r#"
+    .section .text
+    .globl __clone
+    .globl __vfork
+    .align 2
+# __clone(f, stack, flags, arg)
+__clone:
+    # 把 f 和 arg 放在子进程的栈顶
+    addi a1, a1, -16
+    sd a0, 0(a1)
+    sd a3, 8(a1)
+    # sys_clone(flags, stack)
+    mv a0, a2
+    li a7, 403
+    ecall
+    beqz a0, 1f
+    ret
+1:
+    # 子进程已经在新栈上，调用 f(arg)，返回后 exit
+    ld a1, 0(sp)
+    ld a0, 8(sp)
+    jalr a1
+    li a7, 93
+    ecall
+# __vfork()，不使用栈
+__vfork:
+    # sys_clone(CLONE_VM | CLONE_VFORK, 0)
+    li a0, 0x4100
+    li a1, 0
+    li a7, 403
+    ecall
+    ret
+"#
+);
+
+extern "C" {
+    pub(crate) fn __clone(
+        f: extern "C" fn(usize) -> i32,
+        stack: usize,
+        flags: usize,
+        arg: usize,
+    ) -> isize;
+    pub(crate) fn __vfork() -> isize;
+}
+
 pub fn sys_waitpid(pid: isize, xstatus: *mut i32) -> isize {
     syscall(SYSCALL_WAITPID, [pid as usize, xstatus as usize, 0])
 }
@@ -181,6 +250,14 @@ pub fn sys_set_priority(prio: isize) -> isize {
     syscall(SYSCALL_SET_PRIORITY, [prio as usize, 0, 0])
 }
 
+pub fn sys_set_tickets(tickets: isize) -> isize {
+    syscall(SYSCALL_SET_TICKETS, [tickets as usize, 0, 0])
+}
+
+pub fn sys_transfer_tickets(pid: usize, tickets: usize) -> isize {
+    syscall(SYSCALL_TRANSFER_TICKETS, [pid, tickets, 0])
+}
+
 pub fn sys_mmap(start: usize, len: usize, prot: usize) -> isize {
     syscall(SYSCALL_MMAP, [start, len, prot])
 }
@@ -189,10 +266,33 @@ pub fn sys_munmap(start: usize, len: usize) -> isize {
     syscall(SYSCALL_MUNMAP, [start, len, 0])
 }
 
+pub fn sys_mprotect(start: usize, len: usize, prot: usize) -> isize {
+    syscall(SYSCALL_MPROTECT, [start, len, prot])
+}
+
+pub fn sys_shm_open(key: usize, len: usize, prot: usize) -> isize {
+    syscall(SYSCALL_SHM_OPEN, [key, len, prot])
+}
+
+pub fn sys_brk(addr: usize) -> isize {
+    syscall(SYSCALL_BRK, [addr, 0, 0])
+}
+
 pub fn sys_spawn(path: &str) -> isize {
     syscall(SYSCALL_SPAWN, [path.as_ptr() as usize, 0, 0])
 }
 
+pub fn sys_spawn_with(path: &str, args: &[*const u8], attr: &SpawnAttr) -> isize {
+    syscall(
+        SYSCALL_SPAWN,
+        [
+            path.as_ptr() as usize,
+            args.as_ptr() as usize,
+            attr as *const _ as usize,
+        ],
+    )
+}
+
 pub fn sys_dup(fd: usize) -> isize {
     syscall(SYSCALL_DUP, [fd, 0, 0])
 }